@@ -8,12 +8,10 @@ use tokio_stream::StreamExt;
 #[tokio::main]
 async fn main() -> claude_code_rs::Result<()> {
     // Hook that blocks rm/rmdir commands in Bash tool.
-    let block_dangerous = HookDefinition {
-        event: HookEvent::PreToolUse,
-        matcher: HookMatcher {
-            tool_name: Some("Bash".into()),
-        },
-        callback: hook_callback(|input| async move {
+    let block_dangerous = HookDefinition::new(
+        HookEvent::PreToolUse,
+        HookMatcher::tool("Bash"),
+        hook_callback(|input| async move {
             if let HookInput::PreToolUse(pre) = &input {
                 let cmd = pre
                     .tool_input
@@ -28,10 +26,10 @@ async fn main() -> claude_code_rs::Result<()> {
             }
             HookOutput::approve()
         }),
-    };
+    );
 
     let options = ClaudeAgentOptions {
-        permission_mode: PermissionMode::AcceptAll,
+        permission_mode: PermissionMode::BypassPermissions,
         max_turns: Some(3),
         hooks: vec![block_dangerous],
         ..Default::default()
@@ -46,7 +46,7 @@ async fn main() -> claude_code_rs::Result<()> {
     let server = SdkMcpServer::new(vec![add_tool, multiply_tool]);
 
     let options = ClaudeAgentOptions {
-        permission_mode: PermissionMode::AcceptAll,
+        permission_mode: PermissionMode::BypassPermissions,
         max_turns: Some(5),
         ..Default::default()
     };
@@ -3,7 +3,7 @@ use claude_code_rs::{query_text, ClaudeAgentOptions, PermissionMode};
 #[tokio::main]
 async fn main() -> claude_code_rs::Result<()> {
     let options = ClaudeAgentOptions {
-        permission_mode: PermissionMode::AcceptAll,
+        permission_mode: PermissionMode::BypassPermissions,
         max_turns: Some(1),
         ..Default::default()
     };
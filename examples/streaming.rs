@@ -4,7 +4,7 @@ use tokio_stream::StreamExt;
 #[tokio::main]
 async fn main() -> claude_code_rs::Result<()> {
     let options = ClaudeAgentOptions {
-        permission_mode: PermissionMode::AcceptAll,
+        permission_mode: PermissionMode::BypassPermissions,
         max_turns: Some(3),
         ..Default::default()
     };
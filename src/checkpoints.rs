@@ -0,0 +1,433 @@
+//! Git-backed snapshots of the working tree, one per user turn.
+//!
+//! Each checkpoint is a commit of the whole worktree (tracked and untracked
+//! files, minus ignored ones) chained under a hidden ref, so they survive
+//! process restarts and never touch `HEAD`, branches or the index.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use crate::error::{Error, Result};
+use crate::types::hooks::{
+    hook_callback, HookDefinition, HookEvent, HookInput, HookMatcher, HookOutput,
+};
+
+/// Ref that checkpoints are chained under unless set otherwise.
+pub const DEFAULT_CHECKPOINT_REF: &str = "refs/claude/checkpoints";
+
+/// Identity recorded on checkpoint commits, so no git user config is needed.
+const COMMITTER: [(&str, &str); 4] = [
+    ("GIT_AUTHOR_NAME", "claude-code-rs"),
+    ("GIT_AUTHOR_EMAIL", "checkpoints@claude-code-rs"),
+    ("GIT_COMMITTER_NAME", "claude-code-rs"),
+    ("GIT_COMMITTER_EMAIL", "checkpoints@claude-code-rs"),
+];
+
+/// A snapshot of the worktree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Commit id.
+    pub id: String,
+    pub label: String,
+    pub created_at: SystemTime,
+}
+
+/// Checkpoints of a git worktree.
+///
+/// Install [`hook`](Self::hook) to take one before every user turn, or call
+/// [`checkpoint`](Self::checkpoint) directly. [`restore`](Self::restore)
+/// puts the worktree back the way a checkpoint found it.
+///
+/// Cloning gives another handle to the same checkpoints.
+#[derive(Debug, Clone)]
+pub struct GitCheckpoints {
+    root: PathBuf,
+    git_dir: PathBuf,
+    ref_name: String,
+    /// Serializes use of the scratch index.
+    lock: Arc<Mutex<()>>,
+}
+
+impl GitCheckpoints {
+    /// Checkpoints for the repository containing `cwd` (the whole worktree,
+    /// not just `cwd`). Fails if `cwd` is not inside a git worktree.
+    pub async fn open(cwd: impl AsRef<Path>) -> Result<Self> {
+        let cwd = cwd.as_ref();
+        let out = run_git(
+            cwd,
+            &["rev-parse", "--show-toplevel", "--absolute-git-dir"],
+            &[],
+        )
+        .await?;
+        let mut lines = out.lines();
+        let (Some(root), Some(git_dir)) = (lines.next(), lines.next()) else {
+            return Err(Error::Checkpoint(format!(
+                "{} is not inside a git worktree",
+                cwd.display()
+            )));
+        };
+        Ok(Self {
+            root: root.into(),
+            git_dir: git_dir.into(),
+            ref_name: DEFAULT_CHECKPOINT_REF.into(),
+            lock: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// Chain checkpoints under `ref_name` instead, e.g. one ref per session.
+    #[must_use]
+    pub fn with_ref(mut self, ref_name: impl Into<String>) -> Self {
+        self.ref_name = ref_name.into();
+        self
+    }
+
+    /// Top of the worktree.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Snapshot the worktree now.
+    pub async fn checkpoint(&self, label: &str) -> Result<Checkpoint> {
+        let _guard = self.lock.lock().await;
+        self.checkpoint_locked(label).await
+    }
+
+    /// Checkpoints taken so far, oldest first.
+    pub async fn list(&self) -> Result<Vec<Checkpoint>> {
+        if self.latest().await?.is_none() {
+            return Ok(Vec::new());
+        }
+        let out = self
+            .git(
+                &[
+                    "log",
+                    "--reverse",
+                    "--format=%H%x00%ct%x00%s",
+                    &self.ref_name,
+                ],
+                &[],
+            )
+            .await?;
+        Ok(out
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\0');
+                let id = fields.next()?;
+                let secs = fields.next()?.parse().ok()?;
+                Some(Checkpoint {
+                    id: id.into(),
+                    created_at: UNIX_EPOCH + Duration::from_secs(secs),
+                    label: fields.next().unwrap_or_default().into(),
+                })
+            })
+            .collect())
+    }
+
+    /// Unified diff from checkpoint `from` to checkpoint `to`, or to the
+    /// current worktree if `to` is `None`.
+    pub async fn diff(&self, from: &str, to: Option<&str>) -> Result<String> {
+        let from = self.commit_id(from).await?;
+        let to = match to {
+            Some(to) => self.commit_id(to).await?,
+            None => {
+                let _guard = self.lock.lock().await;
+                self.snapshot_tree().await?
+            }
+        };
+        self.git(&["diff", "--no-color", "--no-ext-diff", &from, &to], &[])
+            .await
+    }
+
+    /// Put the worktree back to checkpoint `id`: files are restored, files
+    /// added since are deleted. The current state is checkpointed first and
+    /// returned, so the restore itself can be undone.
+    pub async fn restore(&self, id: &str) -> Result<Checkpoint> {
+        let _guard = self.lock.lock().await;
+        let target = self.commit_id(id).await?;
+        let backup = self
+            .checkpoint_locked(&format!("before restoring {}", short(&target)))
+            .await?;
+
+        let added = self
+            .git(
+                &[
+                    "diff",
+                    "--name-only",
+                    "-z",
+                    "--no-renames",
+                    "--diff-filter=D",
+                    &backup.id,
+                    &target,
+                ],
+                &[],
+            )
+            .await?;
+        for path in added.split('\0').filter(|p| !p.is_empty()) {
+            match std::fs::remove_file(self.root.join(path)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+
+        let index = self.scratch_index();
+        let env = [("GIT_INDEX_FILE", index.as_os_str())];
+        self.git(&["read-tree", &target], &env).await?;
+        self.git(&["checkout-index", "--all", "--force"], &env)
+            .await?;
+        tracing::info!(checkpoint = %target, backup = %backup.id, "restored checkpoint");
+        Ok(backup)
+    }
+
+    /// A UserPromptSubmit hook that checkpoints before each user turn,
+    /// labelled with the prompt. Failures are logged, not fatal.
+    pub fn hook(&self) -> HookDefinition {
+        let checkpoints = self.clone();
+        HookDefinition::new(
+            HookEvent::UserPromptSubmit,
+            HookMatcher::default(),
+            hook_callback(move |input| {
+                let checkpoints = checkpoints.clone();
+                async move {
+                    let label = match &input {
+                        HookInput::UserPromptSubmit(input) => {
+                            input.prompt.lines().next().unwrap_or_default().to_string()
+                        }
+                        _ => String::new(),
+                    };
+                    let label = match label.trim() {
+                        "" => "user turn".to_string(),
+                        label => truncate(label, 72),
+                    };
+                    if let Err(e) = checkpoints.checkpoint(&label).await {
+                        tracing::warn!("failed to checkpoint worktree: {e}");
+                    }
+                    HookOutput::default()
+                }
+            }),
+        )
+    }
+
+    async fn checkpoint_locked(&self, label: &str) -> Result<Checkpoint> {
+        let tree = self.snapshot_tree().await?;
+        let parent = self.latest().await?;
+        let mut args = vec!["commit-tree", tree.as_str(), "-m", label];
+        if let Some(parent) = &parent {
+            args.extend(["-p", parent.as_str()]);
+        }
+        let env = COMMITTER.map(|(k, v)| (k, OsStr::new(v)));
+        let id = self.git(&args, &env).await?.trim().to_string();
+
+        // Compare-and-swap so a concurrent writer can't drop checkpoints.
+        let old = parent.unwrap_or_default();
+        self.git(
+            &["update-ref", "-m", "checkpoint", &self.ref_name, &id, &old],
+            &[],
+        )
+        .await?;
+        Ok(Checkpoint {
+            id,
+            label: label.into(),
+            created_at: SystemTime::now(),
+        })
+    }
+
+    /// Write the worktree into a tree object without touching the real index.
+    async fn snapshot_tree(&self) -> Result<String> {
+        let index = self.scratch_index();
+        // Start from the real index so unchanged files aren't rehashed.
+        match std::fs::copy(self.git_dir.join("index"), &index) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let _ = std::fs::remove_file(&index);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        let env = [("GIT_INDEX_FILE", index.as_os_str())];
+        self.git(&["add", "--all", "--", "."], &env).await?;
+        Ok(self.git(&["write-tree"], &env).await?.trim().to_string())
+    }
+
+    async fn latest(&self) -> Result<Option<String>> {
+        let args = ["rev-parse", "--quiet", "--verify", &self.ref_name];
+        let output = git_output(&self.root, &args, &[]).await?;
+        // `--verify --quiet` exits 1 without a message for a missing ref.
+        if output.status.code() == Some(1) && output.stderr.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(stdout(&args, output)?.trim().to_string()))
+    }
+
+    /// Full commit id of a caller-supplied revision.
+    async fn commit_id(&self, rev: &str) -> Result<String> {
+        // Git would take a leading `-` as an option.
+        if rev.starts_with('-') {
+            return Err(Error::Checkpoint(format!("invalid checkpoint id: {rev}")));
+        }
+        let commit = format!("{rev}^{{commit}}");
+        Ok(self
+            .git(&["rev-parse", "--verify", &commit], &[])
+            .await?
+            .trim()
+            .to_string())
+    }
+
+    fn scratch_index(&self) -> PathBuf {
+        self.git_dir.join("claude-checkpoint-index")
+    }
+
+    async fn git(&self, args: &[&str], env: &[(&str, &OsStr)]) -> Result<String> {
+        run_git(&self.root, args, env).await
+    }
+}
+
+async fn run_git(dir: &Path, args: &[&str], env: &[(&str, &OsStr)]) -> Result<String> {
+    stdout(args, git_output(dir, args, env).await?)
+}
+
+async fn git_output(
+    dir: &Path,
+    args: &[&str],
+    env: &[(&str, &OsStr)],
+) -> Result<std::process::Output> {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .envs(env.iter().copied())
+        .output()
+        .await
+        .map_err(|e| Error::Checkpoint(format!("failed to run git: {e}")))
+}
+
+/// Stdout of a successful git command; its stderr as an error otherwise.
+fn stdout(args: &[&str], output: std::process::Output) -> Result<String> {
+    if !output.status.success() {
+        return Err(Error::Checkpoint(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| Error::Checkpoint("git printed invalid UTF-8".into()))
+}
+
+fn short(id: &str) -> &str {
+    &id[..id.len().min(12)]
+}
+
+fn truncate(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", &s[..end]),
+        None => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        std::fs::write(root.join(path), content).unwrap();
+    }
+
+    fn read(root: &Path, path: &str) -> Option<String> {
+        std::fs::read_to_string(root.join(path)).ok()
+    }
+
+    #[tokio::test]
+    async fn checkpoint_diff_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        run_git(root, &["init", "--quiet"], &[]).await.unwrap();
+        write(root, ".gitignore", "target/\n");
+        write(root, "a.txt", "one\n");
+
+        let checkpoints = GitCheckpoints::open(root).await.unwrap();
+        assert!(checkpoints.list().await.unwrap().is_empty());
+        let first = checkpoints.checkpoint("first turn").await.unwrap();
+
+        write(root, "a.txt", "two\n");
+        write(root, "b.txt", "new\n");
+        std::fs::create_dir(root.join("target")).unwrap();
+        write(root, "target/out", "ignored\n");
+        let second = checkpoints.checkpoint("second turn").await.unwrap();
+
+        let list = checkpoints.list().await.unwrap();
+        assert_eq!(
+            list.iter().map(|c| c.label.as_str()).collect::<Vec<_>>(),
+            ["first turn", "second turn"]
+        );
+        assert_eq!(list[1].id, second.id);
+
+        let diff = checkpoints.diff(&first.id, Some(&second.id)).await.unwrap();
+        assert!(diff.contains("-one\n+two\n"));
+        assert!(diff.contains("+++ b/b.txt"));
+        assert!(!diff.contains("target/out"));
+
+        write(root, "a.txt", "three\n");
+        let to_worktree = checkpoints.diff(&second.id, None).await.unwrap();
+        assert!(to_worktree.contains("-two\n+three\n"));
+
+        let backup = checkpoints.restore(&first.id).await.unwrap();
+        assert_eq!(read(root, "a.txt").as_deref(), Some("one\n"));
+        assert_eq!(read(root, "b.txt"), None);
+        assert_eq!(read(root, "target/out").as_deref(), Some("ignored\n"));
+
+        // The restore can itself be undone.
+        checkpoints.restore(&backup.id).await.unwrap();
+        assert_eq!(read(root, "a.txt").as_deref(), Some("three\n"));
+        assert_eq!(read(root, "b.txt").as_deref(), Some("new\n"));
+        assert_eq!(checkpoints.list().await.unwrap().len(), 4);
+
+        // Nothing touched HEAD or the real index.
+        let status = run_git(root, &["status", "--porcelain"], &[])
+            .await
+            .unwrap();
+        assert!(status.lines().all(|l| l.starts_with("??")));
+    }
+
+    #[tokio::test]
+    async fn option_like_ids_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        run_git(root, &["init", "--quiet"], &[]).await.unwrap();
+        write(root, "a.txt", "one\n");
+        let checkpoints = GitCheckpoints::open(root).await.unwrap();
+        let first = checkpoints.checkpoint("first").await.unwrap();
+
+        let out = root.join("out.txt").display().to_string();
+        let err = checkpoints
+            .diff(&first.id, Some(&format!("--output={out}")))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Checkpoint(_)));
+        assert!(!root.join("out.txt").exists());
+        assert!(checkpoints.restore("--all").await.is_err());
+        assert_eq!(checkpoints.list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn git_failures_are_errors_not_an_empty_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        run_git(root, &["init", "--quiet"], &[]).await.unwrap();
+        let checkpoints = GitCheckpoints::open(root).await.unwrap();
+        assert!(checkpoints.list().await.unwrap().is_empty());
+
+        std::fs::remove_dir_all(root.join(".git")).unwrap();
+        assert!(checkpoints.list().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn open_outside_a_repo_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = GitCheckpoints::open(dir.path()).await.unwrap_err();
+        assert!(matches!(err, Error::Checkpoint(_)));
+    }
+}
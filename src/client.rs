@@ -5,6 +5,7 @@ use std::task::{Context, Poll};
 
 use serde_json::Value;
 use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::Stream;
 
@@ -12,8 +13,9 @@ use crate::error::{Error, Result};
 use crate::mcp::SdkMcpServer;
 use crate::query::{McpMessageHandler, Query};
 use crate::transport::subprocess::SubprocessTransport;
-use crate::types::messages::Message;
+use crate::types::messages::{Message, UserContent, UserInput};
 use crate::types::options::ClaudeAgentOptions;
+use crate::types::permissions::PermissionMode;
 
 /// RAII guard that returns the receiver back to the client on drop.
 ///
@@ -151,22 +153,42 @@ impl ClaudeSDKClient {
             self.options.can_use_tool.clone(),
             mcp_handler,
             self.options.control_timeout,
-        );
+        )
+        .with_audit_log(self.options.audit_log.clone());
 
         let rx = q.connect().await?;
         self.message_rx = Some(rx);
         self.query = Some(q);
 
         if let Some(prompt) = initial_prompt {
-            self.query_ref()?.send_message(prompt, None).await?;
+            self.query_ref()?.send_message(prompt.into(), None).await?;
         }
 
         Ok(())
     }
 
     /// Send a query/prompt. Optionally provide a session_id for resuming.
-    pub async fn query(&self, prompt: &str, session_id: Option<&str>) -> Result<()> {
-        self.query_ref()?.send_message(prompt, session_id).await
+    ///
+    /// Accepts plain text or a [`UserContent`] with image and document blocks.
+    pub async fn query(
+        &self,
+        prompt: impl Into<UserContent>,
+        session_id: Option<&str>,
+    ) -> Result<()> {
+        self.query_ref()?.send_message(prompt.into(), session_id).await
+    }
+
+    /// Forward user messages from a stream as they arrive.
+    ///
+    /// Messages are written in a background task, so follow-up instructions
+    /// can be queued while Claude is still working on earlier ones. Keep
+    /// consuming [`receive_messages()`](Self::receive_messages) meanwhile.
+    /// The returned handle resolves once the stream is exhausted.
+    pub fn send_stream<S>(&self, stream: S) -> Result<JoinHandle<Result<()>>>
+    where
+        S: Stream<Item = UserInput> + Send + 'static,
+    {
+        self.query_ref()?.stream_input(stream)
     }
 
     /// Get a stream of messages from the current query.
@@ -198,7 +220,7 @@ impl ClaudeSDKClient {
     }
 
     /// Change the permission mode.
-    pub async fn set_permission_mode(&self, mode: &str) -> Result<Value> {
+    pub async fn set_permission_mode(&self, mode: PermissionMode) -> Result<Value> {
         self.query_ref()?.set_permission_mode(mode).await
     }
 
@@ -217,6 +239,29 @@ impl ClaudeSDKClient {
         self.query_ref()?.get_mcp_status().await
     }
 
+    /// Id of the current CLI session, once the CLI has reported it.
+    pub fn session_id(&self) -> Option<String> {
+        self.query.as_ref().and_then(Query::session_id)
+    }
+
+    /// Start a second, connected client that resumes this client's session
+    /// as a new branch. Both continue independently from the shared history;
+    /// in-process MCP servers are shared.
+    ///
+    /// Fails with [`Error::NoSession`] until the CLI has reported a session
+    /// id (it does with its first message).
+    pub async fn fork(&self) -> Result<ClaudeSDKClient> {
+        let session_id = self.query_ref()?.session_id().ok_or(Error::NoSession)?;
+        let mut forked = ClaudeSDKClient {
+            options: fork_options(&self.options, session_id),
+            query: None,
+            message_rx: None,
+            mcp_servers: self.mcp_servers.clone(),
+        };
+        forked.connect(None).await?;
+        Ok(forked)
+    }
+
     /// Get server info from the init handshake.
     pub async fn get_server_info(&self) -> Option<Value> {
         match &self.query {
@@ -234,6 +279,15 @@ impl ClaudeSDKClient {
         Ok(())
     }
 
+    /// Exit code of the CLI process, once it has exited.
+    ///
+    /// `None` while it runs, if it was killed by a signal, or when not
+    /// connected. A non-zero code is also reported as
+    /// [`Error::ProcessExit`] on the message stream.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.query.as_ref().and_then(Query::exit_code)
+    }
+
     /// Check if connected.
     pub fn is_connected(&self) -> bool {
         self.query.is_some()
@@ -258,3 +312,42 @@ impl ClaudeSDKClient {
         }))
     }
 }
+
+/// Options for a client branching off `session_id`.
+fn fork_options(options: &ClaudeAgentOptions, session_id: String) -> ClaudeAgentOptions {
+    ClaudeAgentOptions {
+        session_id: None,
+        resume: Some(session_id),
+        continue_session: false,
+        fork_session: true,
+        ..options.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fork_resumes_the_session_as_a_branch() {
+        let options = ClaudeAgentOptions {
+            model: Some("claude-sonnet-4-5".into()),
+            session_id: Some("sess_1".into()),
+            continue_session: true,
+            ..Default::default()
+        };
+        let forked = fork_options(&options, "sess_1".into());
+        assert_eq!(forked.resume.as_deref(), Some("sess_1"));
+        assert_eq!(forked.session_id, None);
+        assert!(forked.fork_session);
+        assert!(!forked.continue_session);
+        assert_eq!(forked.model, options.model);
+    }
+
+    #[tokio::test]
+    async fn fork_requires_a_connection() {
+        let client = ClaudeSDKClient::new(ClaudeAgentOptions::default());
+        assert!(matches!(client.fork().await, Err(Error::NotConnected)));
+        assert_eq!(client.exit_code(), None);
+    }
+}
@@ -48,6 +48,24 @@ pub enum Error {
 
     #[error("hook error: {0}")]
     Hook(String),
+
+    #[error("wall-clock limit of {0:?} exceeded")]
+    WallClockLimitExceeded(std::time::Duration),
+
+    #[error("permission policy error: {0}")]
+    Policy(String),
+
+    #[error("sandbox error: {0}")]
+    Sandbox(String),
+
+    #[error("unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    #[error("checkpoint error: {0}")]
+    Checkpoint(String),
+
+    #[error("no session id received from the CLI yet")]
+    NoSession,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,488 @@
+//! Render conversations as Markdown, self-contained HTML or JSON.
+//!
+//! Thinking, tool calls and tool results become collapsible `<details>`
+//! sections; `Result` messages show cost and usage. System and unknown
+//! messages only appear in the JSON export, which keeps everything in the
+//! stream-json shape and reads back with [`from_json`].
+
+use std::fmt::Write as _;
+
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::message_parser::{message_to_value, parse_message};
+use crate::sessions::SessionStore;
+use crate::types::content::{ContentBlock, ToolResultBlock, ToolResultContent};
+use crate::types::messages::{Message, ResultMessage, UserContent};
+use crate::types::tools::BuiltinToolInput;
+
+/// An export format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+/// Render `messages` in `format`.
+pub fn export(messages: &[Message], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Markdown => to_markdown(messages),
+        ExportFormat::Html => to_html(messages),
+        ExportFormat::Json => to_json(messages),
+    }
+}
+
+/// Render a stored session in `format`.
+///
+/// The transcript is loaded as [`Message`]s first, so fields only the
+/// transcript has (`timestamp`, `cwd`, ...) are dropped even from the JSON
+/// export.
+pub fn export_session(
+    store: &SessionStore,
+    session_id: &str,
+    format: ExportFormat,
+) -> Result<String> {
+    Ok(export(&store.load(session_id)?, format))
+}
+
+/// The messages as a JSON array in the CLI's stream-json shape.
+pub fn to_json(messages: &[Message]) -> String {
+    let values: Vec<Value> = messages.iter().map(message_to_value).collect();
+    serde_json::to_string_pretty(&values).unwrap_or_default()
+}
+
+/// Read back the output of [`to_json`].
+pub fn from_json(json: &str) -> Result<Vec<Message>> {
+    let values: Vec<Value> = serde_json::from_str(json)?;
+    values.into_iter().map(parse_message).collect()
+}
+
+/// The conversation as Markdown, with `<details>` for the collapsible parts.
+pub fn to_markdown(messages: &[Message]) -> String {
+    let mut out = String::new();
+    for part in conversation(messages) {
+        match part {
+            Part::Heading(role) => {
+                let _ = write!(out, "## {role}\n\n");
+            }
+            Part::Text(text) => {
+                let _ = write!(out, "{}\n\n", markdown_text(text.trim_end()));
+            }
+            Part::Details {
+                summary,
+                body,
+                lang,
+            } => {
+                let _ = write!(
+                    out,
+                    "<details>\n<summary>{}</summary>\n\n{}\n</details>\n\n",
+                    escape_html(&summary),
+                    code_fence(&body, lang)
+                );
+            }
+            Part::Result(line) => {
+                let _ = write!(out, "---\n\n**{line}**\n\n");
+            }
+        }
+    }
+    out
+}
+
+/// The conversation as a standalone HTML page.
+pub fn to_html(messages: &[Message]) -> String {
+    let mut body = String::new();
+    let mut open = false;
+    for part in conversation(messages) {
+        match part {
+            Part::Heading(role) => {
+                if open {
+                    body.push_str("</section>\n");
+                }
+                let _ = writeln!(
+                    body,
+                    "<section class=\"{}\">\n<h2>{role}</h2>",
+                    role.to_ascii_lowercase()
+                );
+                open = true;
+            }
+            Part::Text(text) => {
+                let _ = writeln!(
+                    body,
+                    "<div class=\"text\">{}</div>",
+                    escape_html(text.trim_end())
+                );
+            }
+            Part::Details {
+                summary,
+                body: content,
+                ..
+            } => {
+                let _ = writeln!(
+                    body,
+                    "<details>\n<summary>{}</summary>\n<pre>{}</pre>\n</details>",
+                    escape_html(&summary),
+                    escape_html(&content)
+                );
+            }
+            Part::Result(line) => {
+                if open {
+                    body.push_str("</section>\n");
+                    open = false;
+                }
+                let _ = writeln!(body, "<p class=\"result\">{}</p>", escape_html(&line));
+            }
+        }
+    }
+    if open {
+        body.push_str("</section>\n");
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Conversation</title>\n<style>\n{HTML_STYLE}</style>\n</head>\n<body>\n{body}</body>\n</html>\n"
+    )
+}
+
+const HTML_STYLE: &str = "\
+body { font-family: system-ui, sans-serif; max-width: 50rem; margin: 2rem auto; padding: 0 1rem; line-height: 1.5; }
+section { border-left: 3px solid #ccc; padding-left: 1rem; margin-bottom: 1.5rem; }
+section.assistant { border-color: #d97757; }
+h2 { font-size: 1rem; margin: 0 0 .5rem; }
+.text { white-space: pre-wrap; }
+details { margin: .5rem 0; }
+summary { cursor: pointer; color: #555; }
+pre { background: #f6f6f6; padding: .5rem; overflow-x: auto; white-space: pre-wrap; }
+.result { color: #555; border-top: 1px solid #ddd; padding-top: .5rem; }
+";
+
+/// Format-independent pieces of a rendered conversation.
+enum Part {
+    Heading(&'static str),
+    Text(String),
+    Details {
+        summary: String,
+        body: String,
+        lang: &'static str,
+    },
+    Result(String),
+}
+
+fn conversation(messages: &[Message]) -> Vec<Part> {
+    let mut parts = Vec::new();
+    for message in messages {
+        match message {
+            Message::Assistant { message } => {
+                parts.push(Part::Heading("Assistant"));
+                blocks(&message.content, &mut parts);
+            }
+            Message::User { message } => match &message.content {
+                UserContent::Text(text) => {
+                    parts.push(Part::Heading("User"));
+                    parts.push(Part::Text(text.clone()));
+                }
+                UserContent::Blocks(content) => {
+                    // Tool results belong with the assistant's tool calls.
+                    let only_results = content
+                        .iter()
+                        .all(|b| matches!(b, ContentBlock::ToolResult { .. }));
+                    if !only_results {
+                        parts.push(Part::Heading("User"));
+                    }
+                    blocks(content, &mut parts);
+                }
+                UserContent::Empty => {}
+            },
+            Message::Result { result } => parts.push(Part::Result(result_line(result))),
+            Message::System { .. } | Message::Unknown { .. } => {}
+        }
+    }
+    parts
+}
+
+fn blocks(content: &[ContentBlock], parts: &mut Vec<Part>) {
+    for block in content {
+        match block {
+            ContentBlock::Text { text } => parts.push(Part::Text(text.clone())),
+            ContentBlock::Thinking { thinking, .. } => parts.push(Part::Details {
+                summary: "Thinking".into(),
+                body: thinking.clone(),
+                lang: "",
+            }),
+            ContentBlock::ToolUse { name, input, .. } => parts.push(Part::Details {
+                summary: tool_summary(block, name),
+                body: serde_json::to_string_pretty(input).unwrap_or_default(),
+                lang: "json",
+            }),
+            ContentBlock::ToolResult {
+                content, is_error, ..
+            } => parts.push(Part::Details {
+                summary: if *is_error {
+                    "Tool error"
+                } else {
+                    "Tool result"
+                }
+                .into(),
+                body: tool_result_text(content),
+                lang: "",
+            }),
+            ContentBlock::Image { source } => {
+                parts.push(Part::Text(format!("[image: {}]", source.media_type)))
+            }
+            ContentBlock::Document { source, title } => parts.push(Part::Text(format!(
+                "[document: {}]",
+                title.as_deref().unwrap_or(&source.media_type)
+            ))),
+        }
+    }
+}
+
+/// `Tool call: Bash: cargo test`, with the most telling input for built-ins.
+fn tool_summary(block: &ContentBlock, name: &str) -> String {
+    let detail = match block.tool_input() {
+        Some(BuiltinToolInput::Bash(bash)) => Some(bash.command),
+        Some(BuiltinToolInput::Glob(glob)) => Some(glob.pattern),
+        Some(BuiltinToolInput::Grep(grep)) => Some(grep.pattern),
+        Some(BuiltinToolInput::WebFetch(fetch)) => Some(fetch.url),
+        Some(BuiltinToolInput::WebSearch(search)) => Some(search.query),
+        Some(BuiltinToolInput::Task(task)) => Some(task.description),
+        Some(input) => input.path().map(String::from),
+        None => None,
+    };
+    match detail {
+        Some(detail) => {
+            let detail = detail.lines().next().unwrap_or_default();
+            format!("Tool call: {name}: {detail}")
+        }
+        None => format!("Tool call: {name}"),
+    }
+}
+
+fn tool_result_text(content: &ToolResultContent) -> String {
+    match content {
+        ToolResultContent::Text(text) => text.clone(),
+        ToolResultContent::Blocks(blocks) => blocks
+            .iter()
+            .map(|block| match block {
+                ToolResultBlock::Text { text } => text.clone(),
+                ToolResultBlock::Image { source } => format!("[image: {}]", source.media_type),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// `Result: success · 3 turns · 12.3s · $0.0421 · 1200 in / 340 out tokens`.
+fn result_line(result: &ResultMessage) -> String {
+    let mut fields = vec![result.subtype.clone().unwrap_or_else(|| "done".into())];
+    if let Some(error) = result.error.as_ref().filter(|_| result.is_error) {
+        fields.push(format!("error: {error}"));
+    }
+    if let Some(turns) = result.num_turns {
+        fields.push(format!("{turns} turn{}", if turns == 1 { "" } else { "s" }));
+    }
+    if let Some(ms) = result.duration_ms {
+        fields.push(format!("{:.1}s", ms / 1000.0));
+    }
+    if let Some(cost) = result.total_cost_usd.or(result.cost_usd) {
+        fields.push(format!("${cost:.4}"));
+    }
+    if let Some(usage) = &result.usage {
+        let tokens = |n: Option<u64>| n.unwrap_or(0);
+        let mut line = format!(
+            "{} in / {} out tokens",
+            tokens(usage.input_tokens),
+            tokens(usage.output_tokens)
+        );
+        let cached = tokens(usage.cache_read_input_tokens);
+        if cached > 0 {
+            let _ = write!(line, " ({cached} cached)");
+        }
+        fields.push(line);
+    }
+    format!("Result: {}", fields.join(" · "))
+}
+
+/// A fenced code block that `body` can't break out of.
+fn code_fence(body: &str, lang: &str) -> String {
+    let longest_run = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{fence}{lang}\n{}\n{fence}\n", body.trim_end_matches('\n'))
+}
+
+/// Message text with HTML escaped outside code, so it can't open or close
+/// tags around it. A code fence it leaves open is closed.
+fn markdown_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut open_fence: Option<&str> = None;
+    for line in text.split_inclusive('\n') {
+        let fence = fence_marker(line);
+        match open_fence {
+            Some(open) => {
+                let closes = fence.is_some_and(|f| {
+                    f.starts_with(open) && line.trim_start()[f.len()..].trim().is_empty()
+                });
+                if closes {
+                    open_fence = None;
+                }
+                out.push_str(line);
+            }
+            None if fence.is_some() => {
+                open_fence = fence;
+                out.push_str(line);
+            }
+            None => out.push_str(&escape_outside_code_spans(line)),
+        }
+    }
+    if let Some(open) = open_fence {
+        let _ = write!(out, "\n{open}");
+    }
+    out
+}
+
+/// The run of backticks or tildes opening a fenced code block on `line`.
+fn fence_marker(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let c = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let len = trimmed.len() - trimmed.trim_start_matches(c).len();
+    (len >= 3).then(|| &trimmed[..len])
+}
+
+fn escape_outside_code_spans(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('`') {
+        out.push_str(&escape_tags(&rest[..start]));
+        let ticks = rest[start..].len() - rest[start..].trim_start_matches('`').len();
+        let after = &rest[start + ticks..];
+        // A span ends at the next run of exactly as many backticks.
+        let mut end = None;
+        let mut offset = 0;
+        while let Some(i) = after[offset..].find('`') {
+            let run_start = offset + i;
+            let run = after[run_start..].len() - after[run_start..].trim_start_matches('`').len();
+            if run == ticks {
+                end = Some(run_start);
+                break;
+            }
+            offset = run_start + run;
+        }
+        match end {
+            Some(end) => {
+                out.push_str(&rest[start..start + ticks + end + ticks]);
+                rest = &after[end + ticks..];
+            }
+            None => {
+                out.push_str(&rest[start..start + ticks]);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(&escape_tags(rest));
+    out
+}
+
+fn escape_tags(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn messages() -> Vec<Message> {
+        [
+            json!({"type": "system", "subtype": "init", "model": "claude-sonnet-4-5"}),
+            json!({"type": "user", "message": {"content": "Why does <Foo> fail?"}}),
+            json!({"type": "assistant", "message": {"content": [
+                {"type": "thinking", "thinking": "Check the tests."},
+                {"type": "tool_use", "id": "t1", "name": "Bash", "input": {"command": "cargo test"}}
+            ]}}),
+            json!({"type": "user", "message": {"content": [
+                {"type": "tool_result", "tool_use_id": "t1", "content": "```\nfailed\n```", "is_error": true}
+            ]}}),
+            json!({"type": "assistant", "message": {"content": [{"type": "text", "text": "Fixed it."}]}}),
+            json!({"type": "result", "subtype": "success", "num_turns": 2, "duration_ms": 1500.0,
+                   "total_cost_usd": 0.0123, "usage": {"input_tokens": 1200, "output_tokens": 340}}),
+        ]
+        .into_iter()
+        .map(|raw| parse_message(raw).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn markdown_collapses_thinking_and_tools() {
+        let md = to_markdown(&messages());
+        assert!(md.starts_with("## User\n\nWhy does &lt;Foo&gt; fail?\n\n## Assistant\n\n"));
+        assert!(
+            md.contains("<details>\n<summary>Thinking</summary>\n\n```\nCheck the tests.\n```\n")
+        );
+        assert!(md.contains("<summary>Tool call: Bash: cargo test</summary>"));
+        // The result's own fence doesn't end the block early.
+        assert!(md.contains("<summary>Tool error</summary>\n\n````\n```\nfailed\n```\n````\n"));
+        // Tool results don't start a new "User" section.
+        assert_eq!(md.matches("## User").count(), 1);
+        assert!(md.ends_with(
+            "**Result: success · 2 turns · 1.5s · $0.0123 · 1200 in / 340 out tokens**\n\n"
+        ));
+    }
+
+    #[test]
+    fn markdown_text_cannot_break_out_of_the_structure() {
+        // Code keeps its `<`; the unclosed fence is closed.
+        let text = "</details><script>x()</script> in `Vec<u8>`\n\
+                    ```rust\nlet v: Vec<u8>;\n```\n```\n<b>";
+        let md = to_markdown(&[parse_message(json!({
+            "type": "assistant", "message": {"content": [{"type": "text", "text": text}]}
+        }))
+        .unwrap()]);
+        assert_eq!(
+            md,
+            "## Assistant\n\n&lt;/details&gt;&lt;script&gt;x()&lt;/script&gt; in `Vec<u8>`\n\
+             ```rust\nlet v: Vec<u8>;\n```\n```\n<b>\n```\n\n"
+        );
+    }
+
+    #[test]
+    fn html_is_escaped_and_self_contained() {
+        let html = to_html(&messages());
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<style>"));
+        assert!(html.contains("Why does &lt;Foo&gt; fail?"));
+        assert!(html.contains("<summary>Tool call: Bash: cargo test</summary>"));
+        assert_eq!(
+            html.matches("<section").count(),
+            html.matches("</section>").count()
+        );
+    }
+
+    #[test]
+    fn json_export_round_trips() {
+        let messages = messages();
+        let json = to_json(&messages);
+        let back = from_json(&json).unwrap();
+        assert_eq!(back.len(), messages.len());
+        assert_eq!(to_json(&back), json);
+        assert!(matches!(&back[0], Message::System { subtype, .. } if subtype == "init"));
+    }
+}
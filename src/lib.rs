@@ -1,9 +1,14 @@
+pub mod checkpoints;
 pub mod client;
 pub mod error;
+pub mod export;
 pub mod mcp;
 pub(crate) mod message_parser;
+pub mod permissions;
 pub(crate) mod query;
 pub mod query_fn;
+pub mod sessions;
+pub mod tracking;
 pub(crate) mod transport;
 pub mod types;
 
@@ -11,18 +16,34 @@ pub mod types;
 pub use error::{Error, Result};
 pub use types::{
     AssistantMessage, ClaudeAgentOptions, ContentBlock, Message, PermissionMode, PermissionResult,
-    ResultMessage, Usage, UserMessage,
+    ResultMessage, Usage, UserContent, UserInput, UserMessage,
 };
 
 // Re-export primary APIs.
 pub use client::{ClaudeSDKClient, MessageStream};
-pub use query_fn::{query, query_collect, query_text};
+pub use query_fn::{query, query_collect, query_stream, query_text};
 
 // Re-export hook helpers.
-pub use types::hooks::{hook_callback, HookDefinition, HookEvent, HookMatcher, HookOutput};
+pub use types::hooks::{
+    hook_callback, HookDefinition, HookEvent, HookFailurePolicy, HookMatcher, HookOutput,
+    InputMatcher,
+};
 
 // Re-export permission helpers.
-pub use types::permissions::permission_callback;
+pub use types::permissions::{permission_callback, PermissionRule, PermissionUpdate};
+pub use permissions::{ApprovalBroker, AuditLog, JsonlFileSink, PermissionPolicy, TerminalPrompter};
+
+// Re-export typed tool inputs.
+pub use types::tools::{parse_tool_input, BuiltinToolInput};
+
+// Re-export tool call tracking.
+pub use tracking::{FileChangeTracker, ToolCall, ToolCallTracker};
+
+// Re-export checkpoints.
+pub use checkpoints::{Checkpoint, GitCheckpoints};
+
+// Re-export session store.
+pub use sessions::{SessionInfo, SessionStore};
 
 // Re-export MCP helpers.
 pub use mcp::{new_tool, McpTool, McpToolResult, SdkMcpServer};
@@ -39,13 +39,30 @@ fn parse_wrapped_message<T: serde::de::DeserializeOwned>(raw: &Value, label: &st
     })
 }
 
+/// Fields of assistant and user messages that sit next to the "message"
+/// field rather than inside it.
+const TOP_LEVEL_FIELDS: [&str; 3] = ["parent_tool_use_id", "session_id", "uuid"];
+
+/// Fill `field` from the wrapper if the inner message didn't set it.
+fn top_level(raw: &Value, field: &mut Option<String>, name: &str) {
+    if field.is_none() {
+        *field = raw.get(name).and_then(|v| v.as_str()).map(String::from);
+    }
+}
+
 fn parse_assistant(raw: Value) -> Result<Message> {
-    let message: AssistantMessage = parse_wrapped_message(&raw, "assistant")?;
+    let mut message: AssistantMessage = parse_wrapped_message(&raw, "assistant")?;
+    top_level(&raw, &mut message.parent_tool_use_id, "parent_tool_use_id");
+    top_level(&raw, &mut message.session_id, "session_id");
+    top_level(&raw, &mut message.uuid, "uuid");
     Ok(Message::Assistant { message })
 }
 
 fn parse_user(raw: Value) -> Result<Message> {
-    let message: UserMessage = parse_wrapped_message(&raw, "user")?;
+    let mut message: UserMessage = parse_wrapped_message(&raw, "user")?;
+    top_level(&raw, &mut message.parent_tool_use_id, "parent_tool_use_id");
+    top_level(&raw, &mut message.session_id, "session_id");
+    top_level(&raw, &mut message.uuid, "uuid");
     Ok(Message::User { message })
 }
 
@@ -72,6 +89,51 @@ fn parse_system(raw: Value) -> Result<Message> {
     })
 }
 
+/// Turn a Message back into the CLI's stream-json shape; the inverse of
+/// [`parse_message`].
+pub(crate) fn message_to_value(message: &Message) -> Value {
+    match message {
+        Message::Assistant { message } => wrap_message("assistant", message),
+        Message::User { message } => wrap_message("user", message),
+        Message::Result { result } => {
+            let mut value = to_object(result);
+            value.insert("type".into(), "result".into());
+            Value::Object(value)
+        }
+        Message::System { subtype, data } => {
+            let mut value = data.as_object().cloned().unwrap_or_default();
+            value.insert("type".into(), "system".into());
+            value.insert("subtype".into(), subtype.clone().into());
+            Value::Object(value)
+        }
+        Message::Unknown { raw, .. } => raw.clone(),
+    }
+}
+
+/// `{"type": .., "message": .., "parent_tool_use_id": .., ...}`, with the
+/// top-level fields moved out of the inner message.
+fn wrap_message<T: serde::Serialize>(kind: &str, message: &T) -> Value {
+    let mut inner = to_object(message);
+    let mut wrapper = serde_json::Map::new();
+    wrapper.insert("type".into(), kind.into());
+    for name in TOP_LEVEL_FIELDS {
+        let value = inner.remove(name);
+        // The CLI always sends `parent_tool_use_id`, as `null` at the top level.
+        if value.is_some() || name == "parent_tool_use_id" {
+            wrapper.insert(name.into(), value.unwrap_or(Value::Null));
+        }
+    }
+    wrapper.insert("message".into(), Value::Object(inner));
+    Value::Object(wrapper)
+}
+
+fn to_object<T: serde::Serialize>(value: &T) -> serde_json::Map<String, Value> {
+    match serde_json::to_value(value) {
+        Ok(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,6 +166,21 @@ mod tests {
         assert!(matches!(msg, Message::User { .. }));
     }
 
+    #[test]
+    fn parse_keeps_parent_tool_use_id() {
+        let raw = serde_json::json!({
+            "type": "assistant",
+            "parent_tool_use_id": "toolu_task",
+            "message": {"content": []}
+        });
+        match parse_message(raw).unwrap() {
+            Message::Assistant { message } => {
+                assert_eq!(message.parent_tool_use_id.as_deref(), Some("toolu_task"));
+            }
+            _ => panic!("expected Assistant"),
+        }
+    }
+
     #[test]
     fn parse_result_message() {
         let raw = serde_json::json!({
@@ -173,4 +250,30 @@ mod tests {
             panic!("expected Assistant");
         }
     }
+
+    #[test]
+    fn message_to_value_round_trips() {
+        let lines = [
+            serde_json::json!({
+                "type": "assistant",
+                "parent_tool_use_id": "toolu_task",
+                "message": {"id": "msg_1", "model": "claude-sonnet-4-5", "content": [
+                    {"type": "text", "text": "Hi"},
+                    {"type": "tool_use", "id": "toolu_1", "name": "Bash", "input": {"command": "ls"}}
+                ], "stop_reason": "tool_use"}
+            }),
+            serde_json::json!({"type": "user", "message": {"content": [
+                {"type": "tool_result", "tool_use_id": "toolu_1", "content": "a.txt", "is_error": false}
+            ]}}),
+            serde_json::json!({"type": "result", "subtype": "success", "total_cost_usd": 0.01, "uuid": "r1"}),
+            serde_json::json!({"type": "system", "subtype": "init", "model": "claude-sonnet-4-5"}),
+            serde_json::json!({"type": "stream_event", "event": {"delta": "x"}}),
+        ];
+        for line in lines {
+            let value = message_to_value(&parse_message(line.clone()).unwrap());
+            let again = message_to_value(&parse_message(value.clone()).unwrap());
+            assert_eq!(value, again);
+            assert_eq!(value["type"], line["type"]);
+        }
+    }
 }
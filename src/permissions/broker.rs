@@ -0,0 +1,265 @@
+//! Human-in-the-loop approvals for `can_use_tool`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::Stream;
+
+use crate::types::permissions::{CanUseToolCallback, CanUseToolInput, PermissionResult};
+
+/// Time an approval may stay pending before the expiry default applies.
+pub const DEFAULT_APPROVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Identifies a pending approval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ApprovalId(pub u64);
+
+impl std::fmt::Display for ApprovalId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+struct Pending {
+    input: CanUseToolInput,
+    respond: oneshot::Sender<PermissionResult>,
+}
+
+struct Inner {
+    pending: Mutex<HashMap<ApprovalId, Pending>>,
+    /// One unbounded sender per [`ApprovalBroker::requests`] stream, so a
+    /// slow reader never misses a request.
+    subscribers: Mutex<Vec<mpsc::UnboundedSender<(ApprovalId, CanUseToolInput)>>>,
+    next_id: AtomicU64,
+    timeout: Duration,
+    on_expiry: PermissionResult,
+}
+
+/// Holds `can_use_tool` requests open until someone approves or denies them.
+///
+/// Install [`callback`](Self::callback) as `ClaudeAgentOptions::can_use_tool`,
+/// show [`requests`](Self::requests) to a human (e.g. in a web UI) and answer
+/// with [`approve`](Self::approve) or [`deny`](Self::deny). Unanswered
+/// requests resolve to the expiry default (deny) after the timeout.
+///
+/// Cloning gives another handle to the same broker.
+#[derive(Clone)]
+pub struct ApprovalBroker {
+    inner: Arc<Inner>,
+}
+
+impl Default for ApprovalBroker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApprovalBroker {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_expiry(
+            DEFAULT_APPROVAL_TIMEOUT,
+            PermissionResult::deny("approval request expired"),
+        )
+    }
+
+    /// A broker whose requests resolve to `on_expiry` after `timeout`.
+    #[must_use]
+    pub fn with_expiry(timeout: Duration, on_expiry: PermissionResult) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                pending: Mutex::new(HashMap::new()),
+                subscribers: Mutex::new(Vec::new()),
+                next_id: AtomicU64::new(1),
+                timeout,
+                on_expiry,
+            }),
+        }
+    }
+
+    /// The `can_use_tool` callback feeding this broker.
+    pub fn callback(&self) -> CanUseToolCallback {
+        let broker = self.clone();
+        Arc::new(move |input| {
+            let broker = broker.clone();
+            Box::pin(async move { broker.request(input).await })
+        })
+    }
+
+    /// Submit a request and wait for its answer (or expiry).
+    pub async fn request(&self, input: CanUseToolInput) -> PermissionResult {
+        let id = ApprovalId(self.inner.next_id.fetch_add(1, Ordering::Relaxed));
+        let (respond, answer) = oneshot::channel();
+        self.lock().insert(
+            id,
+            Pending {
+                input: input.clone(),
+                respond,
+            },
+        );
+        // Forget the request if the CLI stops waiting for it.
+        let _guard = RemoveOnDrop { broker: self, id };
+
+        // No subscribers is fine: `pending()` still lists the request.
+        self.subscribers()
+            .retain(|tx| tx.send((id, input.clone())).is_ok());
+
+        match tokio::time::timeout(self.inner.timeout, answer).await {
+            Ok(Ok(mut result)) => {
+                result.decided_by.get_or_insert_with(|| "approval broker".into());
+                result
+            }
+            _ => {
+                tracing::info!(%id, "approval request expired");
+                self.inner
+                    .on_expiry
+                    .clone()
+                    .decided_by("approval broker (expired)")
+            }
+        }
+    }
+
+    /// New approval requests as they arrive.
+    ///
+    /// Requests made before subscribing are not replayed; use
+    /// [`pending`](Self::pending) to catch up. The stream buffers without
+    /// limit, so a slow reader sees every request, including ones that have
+    /// since been answered or expired.
+    pub fn requests(&self) -> impl Stream<Item = (ApprovalId, CanUseToolInput)> + Send + 'static {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers().push(tx);
+        UnboundedReceiverStream::new(rx)
+    }
+
+    /// Requests still waiting for an answer, oldest first.
+    pub fn pending(&self) -> Vec<(ApprovalId, CanUseToolInput)> {
+        let mut pending: Vec<_> = self
+            .lock()
+            .iter()
+            .map(|(id, p)| (*id, p.input.clone()))
+            .collect();
+        pending.sort_by_key(|(id, _)| id.0);
+        pending
+    }
+
+    /// Allow a pending request. Returns `false` if it is unknown or expired.
+    pub fn approve(&self, id: ApprovalId) -> bool {
+        self.resolve(id, PermissionResult::allow())
+    }
+
+    /// Deny a pending request. Returns `false` if it is unknown or expired.
+    pub fn deny(&self, id: ApprovalId, reason: impl Into<String>) -> bool {
+        self.resolve(id, PermissionResult::deny(reason))
+    }
+
+    /// Answer a pending request with a full result (e.g. with updated input
+    /// or permission updates). Returns `false` if it is unknown or expired.
+    pub fn resolve(&self, id: ApprovalId, result: PermissionResult) -> bool {
+        match self.lock().remove(&id) {
+            Some(pending) => pending.respond.send(result).is_ok(),
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<ApprovalId, Pending>> {
+        self.inner.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn subscribers(
+        &self,
+    ) -> std::sync::MutexGuard<'_, Vec<mpsc::UnboundedSender<(ApprovalId, CanUseToolInput)>>> {
+        self.inner.subscribers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+struct RemoveOnDrop<'a> {
+    broker: &'a ApprovalBroker,
+    id: ApprovalId,
+}
+
+impl Drop for RemoveOnDrop<'_> {
+    fn drop(&mut self) {
+        self.broker.lock().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    fn input(tool: &str) -> CanUseToolInput {
+        CanUseToolInput {
+            tool_name: tool.into(),
+            input: serde_json::json!({}),
+            tool_use_id: None,
+            permission_suggestions: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_wait_for_an_answer() {
+        let broker = ApprovalBroker::new();
+        let mut requests = Box::pin(broker.requests());
+        let callback = broker.callback();
+
+        let write = tokio::spawn(callback(input("Write")));
+        let (write_id, request) = requests.next().await.unwrap();
+        assert_eq!(request.tool_name, "Write");
+
+        let bash = tokio::spawn(callback(input("Bash")));
+        let (bash_id, _) = requests.next().await.unwrap();
+        assert_eq!(
+            broker.pending().iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            [write_id, bash_id]
+        );
+
+        assert!(broker.deny(bash_id, "not now"));
+        assert!(broker.approve(write_id));
+        assert!(!broker.approve(write_id));
+
+        assert!(write.await.unwrap().allowed);
+        assert_eq!(bash.await.unwrap().reason.as_deref(), Some("not now"));
+        assert!(broker.pending().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unanswered_requests_expire_to_the_default() {
+        let broker =
+            ApprovalBroker::with_expiry(Duration::from_secs(30), PermissionResult::allow());
+        let result = broker.request(input("Read")).await;
+        assert!(result.allowed);
+        assert!(broker.pending().is_empty());
+
+        let result = ApprovalBroker::new().request(input("Read")).await;
+        assert!(!result.allowed);
+    }
+
+    #[tokio::test]
+    async fn slow_readers_see_every_request() {
+        let broker = ApprovalBroker::new();
+        let requests = broker.requests();
+        let callback = broker.callback();
+        let tasks: Vec<_> = (0..100)
+            .map(|_| tokio::spawn(callback(input("Bash"))))
+            .collect();
+        while broker.pending().len() < tasks.len() {
+            tokio::task::yield_now().await;
+        }
+
+        let ids: Vec<_> = requests.take(100).map(|(id, _)| id).collect().await;
+        assert_eq!(ids.len(), 100);
+        for id in ids {
+            assert!(broker.approve(id));
+        }
+        for task in tasks {
+            assert!(task.await.unwrap().allowed);
+        }
+    }
+}
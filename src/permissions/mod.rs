@@ -0,0 +1,11 @@
+//! Reusable permission handling built on the `can_use_tool` callback.
+
+pub mod audit;
+pub mod broker;
+pub mod policy;
+pub mod prompter;
+
+pub use audit::{AuditLog, AuditRecord, AuditSink, JsonlFileSink};
+pub use broker::{ApprovalBroker, ApprovalId};
+pub use policy::{PermissionPolicy, PolicyDecision, PolicyVerdict};
+pub use prompter::TerminalPrompter;
@@ -0,0 +1,385 @@
+//! Interactive permission prompts on the controlling terminal.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::types::permissions::{
+    CanUseToolCallback, CanUseToolInput, PermissionResult, PermissionRule, PermissionUpdate,
+};
+
+#[cfg(unix)]
+const TTY_IN: &str = "/dev/tty";
+#[cfg(unix)]
+const TTY_OUT: &str = "/dev/tty";
+#[cfg(windows)]
+const TTY_IN: &str = "CONIN$";
+#[cfg(windows)]
+const TTY_OUT: &str = "CONOUT$";
+
+struct Io {
+    input: Box<dyn BufRead + Send>,
+    output: Box<dyn Write + Send>,
+}
+
+/// Asks on the terminal before each tool use, like the interactive CLI.
+///
+/// Shows the tool and its input (a diff for Edit/MultiEdit/Write, the
+/// command for Bash) and reads `y`es, `n`o, `a`lways or ne`v`er. "Always"
+/// and "never" hold for the rest of the session, per tool, or per exact
+/// command for Bash; "always" is also passed to the CLI as a session rule.
+///
+/// Control characters in the tool input are shown escaped, so the model
+/// can't redraw the prompt with terminal escape sequences.
+#[derive(Clone)]
+pub struct TerminalPrompter {
+    io: Arc<Mutex<Io>>,
+    remembered: Arc<Mutex<HashMap<String, bool>>>,
+    base_dir: Option<PathBuf>,
+}
+
+impl TerminalPrompter {
+    /// Prompt on the controlling terminal, even if stdin/stdout are redirected.
+    pub fn new() -> Result<Self> {
+        let open = |path: &str, write: bool| {
+            OpenOptions::new()
+                .read(!write)
+                .write(write)
+                .open(path)
+                .map_err(|e| Error::Io(std::io::Error::new(e.kind(), format!("{path}: {e}"))))
+        };
+        let input = open(TTY_IN, false)?;
+        let output = open(TTY_OUT, true)?;
+        Ok(Self::with_io(BufReader::new(input), output))
+    }
+
+    /// Prompt on arbitrary streams.
+    pub fn with_io(
+        input: impl BufRead + Send + 'static,
+        output: impl Write + Send + 'static,
+    ) -> Self {
+        Self {
+            io: Arc::new(Mutex::new(Io {
+                input: Box::new(input),
+                output: Box::new(output),
+            })),
+            remembered: Arc::new(Mutex::new(HashMap::new())),
+            base_dir: None,
+        }
+    }
+
+    /// Directory that relative file paths resolve against when showing a
+    /// Write diff. Defaults to the current directory; set it to the
+    /// session's `cwd` if that differs.
+    #[must_use]
+    pub fn with_base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = Some(dir.into());
+        self
+    }
+
+    /// The `can_use_tool` callback that prompts.
+    pub fn callback(&self) -> CanUseToolCallback {
+        let prompter = self.clone();
+        Arc::new(move |input| {
+            let prompter = prompter.clone();
+            Box::pin(async move {
+                tokio::task::spawn_blocking(move || prompter.prompt(&input))
+                    .await
+                    .unwrap_or_else(|e| PermissionResult::deny(format!("prompt failed: {e}")))
+            })
+        })
+    }
+
+    fn prompt(&self, request: &CanUseToolInput) -> PermissionResult {
+        let mut result = self.prompt_once(request);
+        result
+            .decided_by
+            .get_or_insert_with(|| "terminal prompt".into());
+        result
+    }
+
+    fn prompt_once(&self, request: &CanUseToolInput) -> PermissionResult {
+        let key = remember_key(request);
+        if let Some(&allowed) = self
+            .remembered
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+        {
+            let result = if allowed {
+                PermissionResult::allow()
+            } else {
+                PermissionResult::deny(format!("{key} was denied for this session"))
+            };
+            return result.decided_by("terminal prompt (remembered)");
+        }
+
+        // One prompt at a time; concurrent requests wait their turn.
+        let mut io = self.io.lock().unwrap_or_else(|e| e.into_inner());
+        let summary = render_request(request, self.base_dir.as_deref());
+        let answer = match ask(&mut io, &escape_controls(&summary)) {
+            Ok(Some(answer)) => answer,
+            Ok(None) => return PermissionResult::deny("no answer at the permission prompt"),
+            Err(e) => return PermissionResult::deny(format!("permission prompt failed: {e}")),
+        };
+
+        let remember = |allowed| {
+            self.remembered
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(key.clone(), allowed);
+        };
+        match answer {
+            Answer::Yes => PermissionResult::allow(),
+            Answer::No => PermissionResult::deny("denied at the permission prompt"),
+            Answer::Always => {
+                remember(true);
+                PermissionResult::allow()
+                    .with_permission_update(PermissionUpdate::allow_for_session(rule_for(request)))
+            }
+            Answer::Never => {
+                remember(false);
+                PermissionResult::deny("denied at the permission prompt for this session")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Answer {
+    Yes,
+    No,
+    Always,
+    Never,
+}
+
+/// Show `summary` and read answers until one is valid. `None` on end of input.
+fn ask(io: &mut Io, summary: &str) -> std::io::Result<Option<Answer>> {
+    writeln!(io.output, "\n{summary}")?;
+    loop {
+        write!(io.output, "Allow? [y]es / [n]o / [a]lways / ne[v]er: ")?;
+        io.output.flush()?;
+
+        let mut line = String::new();
+        if io.input.read_line(&mut line)? == 0 {
+            writeln!(io.output)?;
+            return Ok(None);
+        }
+        let answer = match line.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => Answer::Yes,
+            "n" | "no" => Answer::No,
+            "a" | "always" => Answer::Always,
+            "v" | "never" => Answer::Never,
+            _ => continue,
+        };
+        return Ok(Some(answer));
+    }
+}
+
+fn remember_key(request: &CanUseToolInput) -> String {
+    match bash_command(request) {
+        Some(command) => format!("Bash({command})"),
+        None => request.tool_name.clone(),
+    }
+}
+
+fn rule_for(request: &CanUseToolInput) -> PermissionRule {
+    let rule = PermissionRule::tool(&request.tool_name);
+    match bash_command(request) {
+        Some(command) => rule.with_content(command),
+        None => rule,
+    }
+}
+
+fn bash_command(request: &CanUseToolInput) -> Option<&str> {
+    (request.tool_name == "Bash")
+        .then(|| request.input.get("command").and_then(Value::as_str))
+        .flatten()
+}
+
+/// Human-readable summary of a tool use.
+fn render_request(request: &CanUseToolInput, base_dir: Option<&Path>) -> String {
+    let input = &request.input;
+    let field = |name: &str| input.get(name).and_then(Value::as_str).unwrap_or("");
+
+    match request.tool_name.as_str() {
+        "Bash" => {
+            let mut out = format!("Bash wants to run:\n  $ {}", field("command"));
+            if !field("description").is_empty() {
+                out.push_str(&format!("\n  ({})", field("description")));
+            }
+            out
+        }
+        "Edit" => format!(
+            "Edit wants to change {}:\n{}",
+            field("file_path"),
+            diff(field("old_string"), field("new_string"))
+        ),
+        "MultiEdit" => {
+            let mut out = format!("MultiEdit wants to change {}:", field("file_path"));
+            for edit in input
+                .get("edits")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                let get = |name| edit.get(name).and_then(Value::as_str).unwrap_or("");
+                out.push('\n');
+                out.push_str(&diff(get("old_string"), get("new_string")));
+            }
+            out
+        }
+        "Write" => {
+            let path = field("file_path");
+            let full_path = match base_dir {
+                Some(dir) => dir.join(path),
+                None => PathBuf::from(path),
+            };
+            let current = std::fs::read_to_string(full_path).unwrap_or_default();
+            format!(
+                "Write wants to write {path}:\n{}",
+                diff(&current, field("content"))
+            )
+        }
+        tool => format!(
+            "{tool} wants to run with:\n{}",
+            serde_json::to_string_pretty(input).unwrap_or_default()
+        ),
+    }
+}
+
+/// Replace control characters other than newline and tab, and bidi
+/// overrides, with visible `\u{..}` escapes.
+fn escape_controls(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        let bidi = matches!(
+            c,
+            '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}'
+        );
+        if (c.is_control() && c != '\n' && c != '\t') || bidi {
+            out.extend(c.escape_unicode());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn diff(old: &str, new: &str) -> String {
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(tool: &str, input: Value) -> CanUseToolInput {
+        CanUseToolInput {
+            tool_name: tool.into(),
+            input,
+            tool_use_id: None,
+            permission_suggestions: vec![],
+        }
+    }
+
+    #[test]
+    fn renders_commands_and_diffs() {
+        let bash = render_request(&request(
+            "Bash",
+            json!({"command": "cargo test", "description": "Run tests"}),
+        ), None);
+        assert_eq!(bash, "Bash wants to run:\n  $ cargo test\n  (Run tests)");
+
+        let edit = render_request(&request(
+            "Edit",
+            json!({"file_path": "src/lib.rs", "old_string": "a\nb\n", "new_string": "a\nc\n"}),
+        ), None);
+        assert!(edit.starts_with("Edit wants to change src/lib.rs:\n"));
+        assert!(edit.contains("-b\n+c\n"));
+    }
+
+    #[test]
+    fn write_diffs_read_the_file_under_the_base_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "old\n").unwrap();
+        let write = request("Write", json!({"file_path": "notes.txt", "content": "new\n"}));
+        let rendered = render_request(&write, Some(dir.path()));
+        assert!(rendered.contains("-old\n+new\n"), "{rendered}");
+    }
+
+    #[test]
+    fn terminal_escapes_are_shown_escaped() {
+        let output = SharedBuffer::default();
+        let prompter = TerminalPrompter::with_io(std::io::Cursor::new("n\n"), output.clone());
+        let command = "rm -rf ~\r\x1b[2K\x1b]0;title\x07$ ls\u{202e}";
+        prompter.prompt(&request("Bash", json!({ "command": command })));
+
+        let shown = output.text();
+        assert!(!shown.contains(['\r', '\x1b', '\x07', '\u{202e}']), "{shown:?}");
+        assert!(shown.contains("rm -rf ~\\u{d}\\u{1b}[2K"), "{shown:?}");
+    }
+
+    /// A writer whose contents the test can read back.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn always_is_remembered_and_sent_as_a_session_rule() {
+        let prompter =
+            TerminalPrompter::with_io(std::io::Cursor::new("maybe\na\nv\n"), std::io::sink());
+        let ls = request("Bash", json!({"command": "ls"}));
+
+        // "maybe" is rejected and re-asked; "a" allows and remembers.
+        let result = prompter.prompt(&ls);
+        assert!(result.allowed);
+        assert_eq!(
+            result.updated_permissions,
+            [PermissionUpdate::allow_for_session(
+                PermissionRule::tool("Bash").with_content("ls")
+            )]
+        );
+        assert!(prompter.prompt(&ls).allowed);
+
+        // A different command prompts again; "v" denies from now on.
+        let rm = request("Bash", json!({"command": "rm -rf /"}));
+        assert!(!prompter.prompt(&rm).allowed);
+        assert!(!prompter.prompt(&rm).allowed);
+    }
+
+    #[test]
+    fn end_of_input_denies() {
+        let prompter = TerminalPrompter::with_io(std::io::Cursor::new(""), std::io::sink());
+        assert!(
+            !prompter
+                .prompt(&request("Read", json!({"file_path": "a"})))
+                .allowed
+        );
+    }
+}
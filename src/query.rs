@@ -4,14 +4,22 @@ use std::time::Duration;
 
 use serde_json::Value;
 use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tokio_stream::{Stream, StreamExt};
 use tokio_util::sync::CancellationToken;
 
 use crate::error::{Error, Result};
 use crate::message_parser::parse_message;
+use crate::permissions::audit::{AuditDecision, AuditLog, AuditRecord, AuditSource};
 use crate::types::control::{SDKCapabilities, SDKControlCommand};
-use crate::types::hooks::{HookDecision, HookDefinition, HookEvent, HookInput};
-use crate::types::messages::Message;
-use crate::types::permissions::{CanUseToolCallback, CanUseToolInput};
+use crate::types::hooks::{
+    CompiledMatcher, HookDecision, HookDefinition, HookEvent, HookFailurePolicy, HookInput,
+    HookOutput, DEFAULT_HOOK_TIMEOUT,
+};
+use crate::types::messages::{Message, UserContent, UserInput};
+use crate::types::permissions::{
+    CanUseToolCallback, CanUseToolInput, PermissionMode, PermissionResult,
+};
 use crate::transport::{Transport, TransportWriter};
 
 const DEFAULT_CONTROL_TIMEOUT: Duration = Duration::from_secs(30);
@@ -31,12 +39,18 @@ pub struct Query {
     transport: Box<dyn Transport>,
     writer: Option<TransportWriter>,
     hooks: Vec<HookDefinition>,
+    matchers: Arc<Vec<CompiledMatcher>>,
     can_use_tool: Option<CanUseToolCallback>,
     mcp_handler: Option<McpMessageHandler>,
+    audit_log: Option<AuditLog>,
     pending_responses: Arc<Mutex<HashMap<String, oneshot::Sender<Value>>>>,
     cancel: CancellationToken,
     control_timeout: Duration,
     server_info: Arc<Mutex<Option<Value>>>,
+    /// Latest session id seen on the stream.
+    session_id: Arc<std::sync::Mutex<Option<String>>>,
+    /// The consumer channel, without keeping it open once the router exits.
+    consumer_tx: Option<mpsc::WeakSender<Result<Message>>>,
 }
 
 impl Query {
@@ -51,21 +65,40 @@ impl Query {
             transport,
             writer: None,
             hooks,
+            matchers: Arc::new(Vec::new()),
             can_use_tool,
             mcp_handler,
+            audit_log: None,
             pending_responses: Arc::new(Mutex::new(HashMap::new())),
             cancel: CancellationToken::new(),
             control_timeout: control_timeout.unwrap_or(DEFAULT_CONTROL_TIMEOUT),
             server_info: Arc::new(Mutex::new(None)),
+            session_id: Arc::new(std::sync::Mutex::new(None)),
+            consumer_tx: None,
         }
     }
 
+    /// Record permission decisions to `audit_log`.
+    #[must_use]
+    pub fn with_audit_log(mut self, audit_log: Option<AuditLog>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
     /// Connect to the CLI and perform the initialization handshake.
     pub async fn connect(&mut self) -> Result<mpsc::Receiver<Result<Message>>> {
+        let matchers = self
+            .hooks
+            .iter()
+            .map(|hook| hook.matcher.compile())
+            .collect::<Result<Vec<_>>>()?;
+        self.matchers = Arc::new(matchers);
+
         let (raw_rx, writer) = self.transport.connect().await?;
         self.writer = Some(writer.clone());
 
         let (consumer_tx, consumer_rx) = mpsc::channel::<Result<Message>>(256);
+        self.consumer_tx = Some(consumer_tx.downgrade());
 
         // Start the message router task.
         self.spawn_router(raw_rx, consumer_tx, writer);
@@ -77,18 +110,32 @@ impl Query {
     }
 
     /// Send a user message to the CLI.
-    pub async fn send_message(&self, prompt: &str, session_id: Option<&str>) -> Result<()> {
+    pub async fn send_message(&self, content: UserContent, session_id: Option<&str>) -> Result<()> {
+        let input = UserInput {
+            content,
+            session_id: session_id.map(str::to_string),
+            parent_tool_use_id: None,
+        };
+        self.send_input(&input).await
+    }
+
+    /// Send a fully specified user message to the CLI.
+    pub async fn send_input(&self, input: &UserInput) -> Result<()> {
         let writer = self.writer.as_ref().ok_or(Error::NotConnected)?;
-        let msg = serde_json::json!({
-            "type": "user",
-            "message": {
-                "role": "user",
-                "content": prompt
-            },
-            "session_id": session_id,
-            "parent_tool_use_id": null
-        });
-        writer.write(msg).await
+        writer.write(user_message_json(input)).await
+    }
+
+    /// Forward user messages from a stream as they arrive.
+    ///
+    /// Runs in a background task that ends when the stream is exhausted,
+    /// the transport closes, or the query is closed.
+    pub fn stream_input<S>(&self, stream: S) -> Result<JoinHandle<Result<()>>>
+    where
+        S: Stream<Item = UserInput> + Send + 'static,
+    {
+        let writer = self.writer.clone().ok_or(Error::NotConnected)?;
+        let cancel = self.cancel.clone();
+        Ok(tokio::spawn(forward_input_stream(stream, writer, cancel)))
     }
 
     /// Send a control command and wait for the response.
@@ -101,7 +148,7 @@ impl Query {
             .await
     }
 
-    pub async fn set_permission_mode(&self, mode: &str) -> Result<Value> {
+    pub async fn set_permission_mode(&self, mode: PermissionMode) -> Result<Value> {
         self.send_control_command(SDKControlCommand::set_permission_mode(mode))
             .await
     }
@@ -125,11 +172,36 @@ impl Query {
         self.server_info.lock().await.clone()
     }
 
+    /// Id of the CLI session, once a message carrying it has arrived.
+    pub fn session_id(&self) -> Option<String> {
+        self.session_id.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
     #[allow(dead_code)]
     pub async fn end_input(&self) -> Result<()> {
         self.transport.end_input().await
     }
 
+    /// Exit code of the CLI process, once it has exited.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.transport.exit_code()
+    }
+
+    /// Deliver `error` to the consumer stream, if it is still open.
+    pub(crate) async fn report_error(&self, error: Error) {
+        match self.consumer_tx.as_ref().and_then(mpsc::WeakSender::upgrade) {
+            Some(tx) => {
+                let _ = tx.send(Err(error)).await;
+            }
+            None => tracing::warn!("dropping error after the message stream closed: {error}"),
+        }
+    }
+
+    /// Whether the query was closed or its router has finished.
+    pub fn is_closed(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
     /// Wait until the cancel token is triggered (transport/router finished).
     pub async fn closed(&self) {
         self.cancel.cancelled().await;
@@ -179,6 +251,7 @@ impl Query {
             "subtype": "initialize",
             "protocol_version": "1",
             "capabilities": capabilities,
+            "hooks": hooks_config(&self.hooks),
         })).await?;
 
         {
@@ -196,12 +269,19 @@ impl Query {
         writer: TransportWriter,
     ) {
         let pending = self.pending_responses.clone();
-        let hooks = self.hooks.clone();
-        let can_use_tool = self.can_use_tool.clone();
-        let mcp_handler = self.mcp_handler.clone();
+        let handlers = Arc::new(ControlHandlers {
+            hooks: self.hooks.clone(),
+            matchers: self.matchers.clone(),
+            can_use_tool: self.can_use_tool.clone(),
+            mcp_handler: self.mcp_handler.clone(),
+            audit_log: self.audit_log.clone(),
+        });
         let cancel = self.cancel.clone();
+        let shared_session_id = self.session_id.clone();
 
         tokio::spawn(async move {
+            // Latest session id seen on the stream, for audit records.
+            let mut session_id: Option<String> = None;
             loop {
                 tokio::select! {
                     _ = cancel.cancelled() => break,
@@ -211,19 +291,39 @@ impl Query {
                                 let msg_type = value.get("type")
                                     .and_then(|v| v.as_str())
                                     .unwrap_or("");
+                                if let Some(sid) = value.get("session_id").and_then(|v| v.as_str()) {
+                                    if session_id.as_deref() != Some(sid) {
+                                        session_id = Some(sid.to_string());
+                                        *shared_session_id.lock().unwrap_or_else(|e| e.into_inner()) =
+                                            session_id.clone();
+                                    }
+                                }
 
                                 match msg_type {
                                     "control_response" => {
                                         route_control_response(&pending, &value).await;
                                     }
                                     "control_request" => {
-                                        dispatch_control_request(
-                                            &value,
-                                            &hooks,
-                                            &can_use_tool,
-                                            &mcp_handler,
-                                            &writer,
-                                        ).await;
+                                        // Callbacks may wait on a human for minutes; answer
+                                        // in the background so messages and control
+                                        // responses keep flowing meanwhile.
+                                        let handlers = handlers.clone();
+                                        let session_id = session_id.clone();
+                                        let writer = writer.clone();
+                                        let consumer_tx = consumer_tx.clone();
+                                        let cancel = cancel.clone();
+                                        tokio::spawn(async move {
+                                            tokio::select! {
+                                                _ = cancel.cancelled() => {}
+                                                _ = dispatch_control_request(
+                                                    &value,
+                                                    &handlers,
+                                                    session_id.as_deref(),
+                                                    &writer,
+                                                    &consumer_tx,
+                                                ) => {}
+                                            }
+                                        });
                                     }
                                     _ => {
                                         let parsed = parse_message(value);
@@ -249,6 +349,39 @@ impl Query {
     }
 }
 
+/// Build the stream-json envelope for a user message.
+fn user_message_json(input: &UserInput) -> Value {
+    serde_json::json!({
+        "type": "user",
+        "message": {
+            "role": "user",
+            "content": input.content
+        },
+        "session_id": input.session_id,
+        "parent_tool_use_id": input.parent_tool_use_id
+    })
+}
+
+async fn forward_input_stream<S>(
+    stream: S,
+    writer: TransportWriter,
+    cancel: CancellationToken,
+) -> Result<()>
+where
+    S: Stream<Item = UserInput> + Send,
+{
+    tokio::pin!(stream);
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            next = stream.next() => match next {
+                Some(input) => writer.write(user_message_json(&input)).await?,
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
 async fn route_control_response(
     pending: &Arc<Mutex<HashMap<String, oneshot::Sender<Value>>>>,
     value: &Value,
@@ -267,12 +400,21 @@ async fn route_control_response(
     }
 }
 
+/// Everything the router needs to answer control requests.
+struct ControlHandlers {
+    hooks: Vec<HookDefinition>,
+    matchers: Arc<Vec<CompiledMatcher>>,
+    can_use_tool: Option<CanUseToolCallback>,
+    mcp_handler: Option<McpMessageHandler>,
+    audit_log: Option<AuditLog>,
+}
+
 async fn dispatch_control_request(
     value: &Value,
-    hooks: &[HookDefinition],
-    can_use_tool: &Option<CanUseToolCallback>,
-    mcp_handler: &Option<McpMessageHandler>,
+    handlers: &ControlHandlers,
+    session_id: Option<&str>,
     writer: &TransportWriter,
+    consumer_tx: &mpsc::Sender<Result<Message>>,
 ) {
     let request_id = value
         .get("request_id")
@@ -294,9 +436,22 @@ async fn dispatch_control_request(
         .unwrap_or("");
 
     let response_body = match subtype {
-        "can_use_tool" => handle_can_use_tool(request, can_use_tool).await,
-        "hook_callback" => handle_hook_callback(request, hooks).await,
-        "mcp_message" => handle_mcp_message(request, mcp_handler).await,
+        "can_use_tool" => {
+            let audit_log = handlers.audit_log.as_ref();
+            handle_can_use_tool(request, &handlers.can_use_tool, audit_log, session_id).await
+        }
+        "hook_callback" => {
+            handle_hook_callback(
+                request,
+                &handlers.hooks,
+                &handlers.matchers,
+                consumer_tx,
+                handlers.audit_log.as_ref(),
+                session_id,
+            )
+            .await
+        }
+        "mcp_message" => handle_mcp_message(request, &handlers.mcp_handler).await,
         other => {
             tracing::warn!(subtype = other, "unknown control request subtype");
             serde_json::json!({"error": format!("unknown subtype: {other}")})
@@ -317,90 +472,405 @@ async fn dispatch_control_request(
     }
 }
 
-async fn handle_can_use_tool(request: &Value, callback: &Option<CanUseToolCallback>) -> Value {
+async fn handle_can_use_tool(
+    request: &Value,
+    callback: &Option<CanUseToolCallback>,
+    audit_log: Option<&AuditLog>,
+    session_id: Option<&str>,
+) -> Value {
     let tool_name = request
         .get("tool_name")
         .and_then(|v| v.as_str())
         .unwrap_or("")
         .to_string();
     let input = request.get("input").cloned().unwrap_or(Value::Null);
+    let tool_use_id = request
+        .get("tool_use_id")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
 
-    if let Some(cb) = callback {
-        let result = cb(CanUseToolInput { tool_name, input }).await;
-        if result.allowed {
-            serde_json::json!({"behavior": "allow"})
-        } else {
-            serde_json::json!({
-                "behavior": "deny",
-                "message": result.reason.unwrap_or_default()
-            })
+    let audit = |decision, reason: Option<&str>, decided_by: Option<&str>| {
+        if let Some(log) = audit_log {
+            log.record(AuditRecord {
+                timestamp: String::new(),
+                session_id: session_id.map(str::to_string),
+                source: AuditSource::CanUseTool,
+                tool_name: tool_name.clone(),
+                tool_use_id: tool_use_id.clone(),
+                input: input.clone(),
+                decision,
+                reason: reason.map(str::to_string),
+                decided_by: decided_by.unwrap_or("can_use_tool callback").to_string(),
+            });
+        }
+    };
+
+    let Some(cb) = callback else {
+        audit(AuditDecision::Allow, None, Some("no can_use_tool callback"));
+        return serde_json::json!({"behavior": "allow", "updatedInput": input});
+    };
+
+    // Skip suggestions this SDK doesn't understand rather than dropping all.
+    let permission_suggestions = request
+        .get("permission_suggestions")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|raw| match serde_json::from_value(raw.clone()) {
+            Ok(suggestion) => Some(suggestion),
+            Err(e) => {
+                tracing::warn!("skipping permission suggestion: {e}");
+                None
+            }
+        })
+        .collect();
+
+    let result = cb(CanUseToolInput {
+        tool_name: tool_name.clone(),
+        input: input.clone(),
+        tool_use_id: tool_use_id.clone(),
+        permission_suggestions,
+    })
+    .await;
+    let decision = if result.allowed {
+        AuditDecision::Allow
+    } else {
+        AuditDecision::Deny
+    };
+    audit(decision, result.reason.as_deref(), result.decided_by.as_deref());
+    permission_result_json(result, input)
+}
+
+/// Serialize a permission result into the CLI's `can_use_tool` response.
+fn permission_result_json(result: PermissionResult, original_input: Value) -> Value {
+    if result.allowed {
+        let mut response = serde_json::json!({
+            "behavior": "allow",
+            "updatedInput": result.updated_input.unwrap_or(original_input),
+        });
+        if !result.updated_permissions.is_empty() {
+            response["updatedPermissions"] =
+                serde_json::to_value(&result.updated_permissions).unwrap_or(Value::Null);
         }
+        response
     } else {
-        serde_json::json!({"behavior": "allow"})
+        let mut response = serde_json::json!({
+            "behavior": "deny",
+            "message": result.reason.unwrap_or_default()
+        });
+        if result.interrupt {
+            response["interrupt"] = true.into();
+        }
+        response
     }
 }
 
-async fn handle_hook_callback(request: &Value, hooks: &[HookDefinition]) -> Value {
+/// Hook registrations for the initialize request, keyed by event name.
+///
+/// Each event gets a single callback, `hook_<Event>`, so that the SDK runs
+/// and combines all hooks for it; see [`run_hooks`]. The CLI-side matcher
+/// is the union of the tool patterns, or omitted if any hook matches all tools.
+fn hooks_config(hooks: &[HookDefinition]) -> Value {
+    let mut config = serde_json::Map::new();
+    let mut patterns: HashMap<&'static str, Option<Vec<String>>> = HashMap::new();
+    for hook in hooks {
+        let event = hook.event.as_str();
+        let regex = hook.matcher.tool_regex();
+        patterns
+            .entry(event)
+            .and_modify(|all| match (all.as_mut(), &regex) {
+                (Some(all), Some(regex)) => all.push(regex.clone()),
+                _ => *all = None,
+            })
+            .or_insert_with(|| regex.clone().map(|r| vec![r]));
+        config.entry(event).or_insert_with(|| {
+            serde_json::json!([{"hookCallbackIds": [format!("hook_{event}")]}])
+        });
+    }
+    for (event, patterns) in patterns {
+        if let Some(patterns) = patterns {
+            config[event][0]["matcher"] = patterns.join("|").into();
+        }
+    }
+    Value::Object(config)
+}
+
+async fn handle_hook_callback(
+    request: &Value,
+    hooks: &[HookDefinition],
+    matchers: &[CompiledMatcher],
+    consumer_tx: &mpsc::Sender<Result<Message>>,
+    audit_log: Option<&AuditLog>,
+    session_id: Option<&str>,
+) -> Value {
     let callback_id = request
         .get("callback_id")
         .and_then(|v| v.as_str())
         .unwrap_or("");
     let hook_input = request.get("input").cloned().unwrap_or(Value::Null);
 
-    let hook_index: Option<usize> = callback_id
-        .strip_prefix("hook_")
-        .and_then(|s| s.parse().ok());
+    let event = callback_id.strip_prefix("hook_").and_then(|name| {
+        hooks
+            .iter()
+            .map(|hook| &hook.event)
+            .find(|event| event.as_str() == name)
+    });
 
-    let hook = hook_index.and_then(|i| hooks.get(i));
+    match event {
+        Some(event) => {
+            let run = run_hooks(event, hook_input.clone(), hooks, matchers).await;
+            for error in run.errors {
+                tracing::warn!("{error}");
+                let _ = consumer_tx.send(Err(error)).await;
+            }
+            if let (Some(log), HookEvent::PreToolUse, true) = (audit_log, event, run.ran) {
+                log.record(hook_audit_record(&hook_input, &run.output, run.decided_by, session_id));
+            }
+            hook_output_json(event, &run.output)
+        }
+        None => {
+            let error = Error::Hook(format!("unknown hook callback {callback_id:?}"));
+            tracing::warn!("{error}");
+            let _ = consumer_tx.send(Err(error)).await;
+            serde_json::json!({"continue": true})
+        }
+    }
+}
 
-    if let Some(hook) = hook {
-        let event_name = hook.event.as_str();
-        let typed_input = match hook.event {
-            HookEvent::PreToolUse => HookInput::PreToolUse(
-                serde_json::from_value(hook_input.clone()).unwrap_or_else(|e| {
-                    tracing::warn!(event = event_name, "hook input parse failed: {e}");
-                    Default::default()
-                }),
-            ),
-            HookEvent::PostToolUse => HookInput::PostToolUse(
-                serde_json::from_value(hook_input.clone()).unwrap_or_else(|e| {
-                    tracing::warn!(event = event_name, "hook input parse failed: {e}");
-                    Default::default()
-                }),
-            ),
-            HookEvent::Notification => HookInput::Notification(
-                serde_json::from_value(hook_input.clone()).unwrap_or_else(|e| {
-                    tracing::warn!(event = event_name, "hook input parse failed: {e}");
-                    Default::default()
-                }),
-            ),
-            HookEvent::Stop | HookEvent::SubagentStop => HookInput::Stop(
-                serde_json::from_value(hook_input.clone()).unwrap_or_else(|e| {
-                    tracing::warn!(event = event_name, "hook input parse failed: {e}");
-                    Default::default()
-                }),
-            ),
+/// Audit record for the outcome of the PreToolUse hooks.
+fn hook_audit_record(
+    raw_input: &Value,
+    output: &HookOutput,
+    decided_by: Option<usize>,
+    session_id: Option<&str>,
+) -> AuditRecord {
+    let field = |name: &str| raw_input.get(name).and_then(|v| v.as_str()).map(str::to_string);
+    let decision = match output.decision {
+        Some(HookDecision::Block) => AuditDecision::Deny,
+        Some(HookDecision::Approve) => AuditDecision::Allow,
+        _ => AuditDecision::Defer,
+    };
+    AuditRecord {
+        timestamp: String::new(),
+        session_id: field("session_id").or_else(|| session_id.map(str::to_string)),
+        source: AuditSource::PreToolUseHook,
+        tool_name: field("tool_name").unwrap_or_default(),
+        tool_use_id: field("tool_use_id"),
+        input: raw_input.get("tool_input").cloned().unwrap_or(Value::Null),
+        decision,
+        reason: output.reason.clone(),
+        decided_by: match decided_by {
+            Some(index) => format!("PreToolUse hook #{index}"),
+            None => "PreToolUse hooks".into(),
+        },
+    }
+}
+
+/// The combined result of running an event's hooks.
+#[derive(Default)]
+struct HookRun {
+    output: HookOutput,
+    /// Hook failures, under either policy.
+    errors: Vec<Error>,
+    /// Whether any hook matched.
+    ran: bool,
+    /// Index of the hook whose decision stands.
+    decided_by: Option<usize>,
+}
+
+/// Run the hooks registered for `event` in order and combine their outputs.
+async fn run_hooks(
+    event: &HookEvent,
+    mut raw_input: Value,
+    hooks: &[HookDefinition],
+    matchers: &[CompiledMatcher],
+) -> HookRun {
+    let is_tool_event = matches!(event, HookEvent::PreToolUse | HookEvent::PostToolUse);
+    let mut run = HookRun::default();
+
+    for (index, (hook, matcher)) in hooks.iter().zip(matchers).enumerate() {
+        if hook.event != *event {
+            continue;
+        }
+        // The CLI filters by tool name too, but input matchers (and older
+        // CLIs) are only evaluated here.
+        if is_tool_event {
+            let tool_name = raw_input
+                .get("tool_name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let tool_input = raw_input.get("tool_input").unwrap_or(&Value::Null);
+            if !matcher.matches(tool_name, tool_input) {
+                continue;
+            }
+        }
+
+        run.ran = true;
+        let output = match run_hook(hook, raw_input.clone()).await {
+            Ok(output) => output,
+            Err(e) => {
+                let output = match hook.on_failure {
+                    HookFailurePolicy::FailOpen => None,
+                    HookFailurePolicy::FailClosed => Some(HookOutput::block(e.to_string())),
+                };
+                run.errors.push(e);
+                match output {
+                    Some(output) => output,
+                    None => continue,
+                }
+            }
         };
 
-        let output = (hook.callback)(typed_input).await;
-        let mut result = serde_json::json!({"continue": true});
-        if let Some(decision) = &output.decision {
-            let hook_specific = serde_json::json!({
-                "hookEventName": hook.event.as_str(),
-                "permissionDecision": decision.as_str(),
-                "permissionDecisionReason": output.reason.as_deref().unwrap_or(""),
-            });
-            result["hookSpecificOutput"] = hook_specific;
+        if *event == HookEvent::PreToolUse {
+            if let Some(ref updated) = output.updated_input {
+                raw_input["tool_input"] = updated.clone();
+            }
+        }
+        if merge_hook_output(&mut run.output, output) {
+            run.decided_by = Some(index);
+        }
+        if run.output.decision == Some(HookDecision::Block)
+            || run.output.should_continue == Some(false)
+        {
+            break;
+        }
+    }
+    run
+}
+
+/// Run one hook with its timeout, turning panics and bad input into errors.
+async fn run_hook(hook: &HookDefinition, raw_input: Value) -> Result<HookOutput> {
+    let event = hook.event.as_str();
+    let input = parse_hook_input(&hook.event, raw_input)?;
+    let timeout = hook.timeout.unwrap_or(DEFAULT_HOOK_TIMEOUT);
 
-            if *decision == HookDecision::Block {
-                result["continue"] = Value::Bool(false);
+    let mut task = tokio::spawn((hook.callback)(input));
+    match tokio::time::timeout(timeout, &mut task).await {
+        Ok(Ok(output)) => Ok(output),
+        Ok(Err(e)) => Err(Error::Hook(format!("{event} hook failed: {e}"))),
+        Err(_) => {
+            task.abort();
+            Err(Error::Hook(format!("{event} hook timed out after {timeout:?}")))
+        }
+    }
+}
+
+/// Fold the next hook's output into the combined output. Returns whether
+/// its decision replaced the combined one.
+fn merge_hook_output(combined: &mut HookOutput, next: HookOutput) -> bool {
+    // Block beats Approve beats no decision (or Ignore); the first hook to
+    // decide keeps its reason.
+    let rank = |decision: &Option<HookDecision>| match decision {
+        Some(HookDecision::Block) => 2,
+        Some(HookDecision::Approve) => 1,
+        _ => 0,
+    };
+    let replace = rank(&next.decision) > rank(&combined.decision);
+    if replace {
+        combined.decision = next.decision;
+        combined.reason = next.reason;
+    }
+
+    if next.updated_input.is_some() {
+        combined.updated_input = next.updated_input;
+    }
+    append_line(&mut combined.additional_context, next.additional_context);
+    append_line(&mut combined.system_message, next.system_message);
+    combined.suppress_output |= next.suppress_output;
+    if next.should_continue.is_some() && combined.should_continue != Some(false) {
+        combined.should_continue = next.should_continue;
+        combined.stop_reason = next.stop_reason;
+    }
+    replace
+}
+
+fn append_line(into: &mut Option<String>, next: Option<String>) {
+    match (into.as_mut(), next) {
+        (Some(text), Some(next)) => {
+            text.push('\n');
+            text.push_str(&next);
+        }
+        (None, next) => *into = next,
+        (Some(_), None) => {}
+    }
+}
+
+/// Serialize a hook's output into the CLI's hook response schema.
+fn hook_output_json(event: &HookEvent, output: &HookOutput) -> Value {
+    let mut result = serde_json::json!({"continue": output.should_continue.unwrap_or(true)});
+    if let Some(ref reason) = output.stop_reason {
+        result["stopReason"] = reason.as_str().into();
+    }
+    if output.suppress_output {
+        result["suppressOutput"] = true.into();
+    }
+    if let Some(ref message) = output.system_message {
+        result["systemMessage"] = message.as_str().into();
+    }
+
+    let reason = output.reason.as_deref().unwrap_or("");
+    let mut specific = serde_json::Map::new();
+    match event {
+        // Top-level decision: "block" feeds `reason` back to Claude (and for
+        // Stop/SubagentStop keeps it working instead of stopping).
+        HookEvent::PostToolUse
+        | HookEvent::UserPromptSubmit
+        | HookEvent::Stop
+        | HookEvent::SubagentStop
+            if output.decision == Some(HookDecision::Block) =>
+        {
+            result["decision"] = "block".into();
+            result["reason"] = reason.into();
+        }
+        // Per-call decision; a denial only stops this tool call.
+        HookEvent::PreToolUse => {
+            if let Some(decision) = output.decision.as_ref().and_then(|d| d.permission_decision()) {
+                specific.insert("permissionDecision".into(), decision.into());
+                specific.insert("permissionDecisionReason".into(), reason.into());
             }
         }
-        result
-    } else {
-        tracing::warn!(callback_id, "hook callback not found");
-        serde_json::json!({"continue": true})
+        _ => {}
+    }
+
+    if *event == HookEvent::PreToolUse {
+        if let Some(ref input) = output.updated_input {
+            specific.insert("updatedInput".into(), input.clone());
+        }
+    }
+    if matches!(
+        event,
+        HookEvent::PostToolUse | HookEvent::UserPromptSubmit | HookEvent::SessionStart
+    ) {
+        if let Some(ref context) = output.additional_context {
+            specific.insert("additionalContext".into(), context.as_str().into());
+        }
+    }
+
+    if !specific.is_empty() {
+        specific.insert("hookEventName".into(), event.as_str().into());
+        result["hookSpecificOutput"] = Value::Object(specific);
+    }
+    result
+}
+
+/// Deserialize raw hook input into the typed input for `event`.
+fn parse_hook_input(event: &HookEvent, raw: Value) -> Result<HookInput> {
+    fn parse<T: serde::de::DeserializeOwned>(event: &HookEvent, raw: Value) -> Result<T> {
+        serde_json::from_value(raw)
+            .map_err(|e| Error::Hook(format!("malformed {} hook input: {e}", event.as_str())))
     }
+
+    Ok(match event {
+        HookEvent::PreToolUse => HookInput::PreToolUse(parse(event, raw)?),
+        HookEvent::PostToolUse => HookInput::PostToolUse(parse(event, raw)?),
+        HookEvent::Notification => HookInput::Notification(parse(event, raw)?),
+        HookEvent::Stop => HookInput::Stop(parse(event, raw)?),
+        HookEvent::SubagentStop => HookInput::SubagentStop(parse(event, raw)?),
+        HookEvent::UserPromptSubmit => HookInput::UserPromptSubmit(parse(event, raw)?),
+        HookEvent::PreCompact => HookInput::PreCompact(parse(event, raw)?),
+        HookEvent::SessionStart => HookInput::SessionStart(parse(event, raw)?),
+        HookEvent::SessionEnd => HookInput::SessionEnd(parse(event, raw)?),
+    })
 }
 
 async fn handle_mcp_message(request: &Value, handler: &Option<McpMessageHandler>) -> Value {
@@ -430,3 +900,511 @@ impl Drop for Query {
         self.cancel.cancel();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::hooks::{hook_callback, HookMatcher, StopInput};
+
+    #[tokio::test]
+    async fn forward_input_stream_writes_each_message() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let writer = TransportWriter::new(tx);
+        let inputs = vec![
+            UserInput::new("first"),
+            UserInput::new("second")
+                .with_session_id("sess_1")
+                .with_parent_tool_use_id("tu_1"),
+        ];
+
+        forward_input_stream(tokio_stream::iter(inputs), writer, CancellationToken::new())
+            .await
+            .unwrap();
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first["type"], "user");
+        assert_eq!(first["message"]["content"], "first");
+        assert_eq!(first["parent_tool_use_id"], Value::Null);
+
+        let second = rx.recv().await.unwrap();
+        assert_eq!(second["message"]["content"], "second");
+        assert_eq!(second["session_id"], "sess_1");
+        assert_eq!(second["parent_tool_use_id"], "tu_1");
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[test]
+    fn parse_hook_input_reads_common_fields() {
+        let raw = serde_json::json!({
+            "session_id": "sess_1",
+            "transcript_path": "/tmp/t.jsonl",
+            "cwd": "/work",
+            "hook_event_name": "SessionStart",
+            "source": "resume"
+        });
+        match parse_hook_input(&HookEvent::SessionStart, raw).unwrap() {
+            HookInput::SessionStart(input) => {
+                assert_eq!(input.source, "resume");
+                assert_eq!(input.base.session_id.as_deref(), Some("sess_1"));
+                assert_eq!(input.base.transcript_path.as_deref(), Some("/tmp/t.jsonl"));
+                assert_eq!(input.base.cwd.as_deref(), Some("/work"));
+                assert_eq!(input.base.hook_event_name.as_deref(), Some("SessionStart"));
+            }
+            other => panic!("expected SessionStart, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_hook_input_keeps_subagent_stop_distinct() {
+        let raw = serde_json::json!({"stop_hook_active": true});
+        let input = parse_hook_input(&HookEvent::SubagentStop, raw).unwrap();
+        assert_eq!(input.event(), HookEvent::SubagentStop);
+        assert!(matches!(input, HookInput::SubagentStop(StopInput { stop_hook_active: true, .. })));
+    }
+
+    #[test]
+    fn parse_hook_input_tool_and_compact_events() {
+        let raw = serde_json::json!({
+            "tool_use_id": "tu_1",
+            "tool_name": "Bash",
+            "tool_input": {"command": "ls"}
+        });
+        match parse_hook_input(&HookEvent::PreToolUse, raw).unwrap() {
+            HookInput::PreToolUse(input) => {
+                assert_eq!(input.tool_name, "Bash");
+                assert_eq!(input.base.tool_use_id.as_deref(), Some("tu_1"));
+            }
+            other => panic!("expected PreToolUse, got {other:?}"),
+        }
+
+        let raw = serde_json::json!({"trigger": "manual", "custom_instructions": "keep tests"});
+        match parse_hook_input(&HookEvent::PreCompact, raw).unwrap() {
+            HookInput::PreCompact(input) => {
+                assert_eq!(input.trigger, "manual");
+                assert_eq!(input.custom_instructions.as_deref(), Some("keep tests"));
+            }
+            other => panic!("expected PreCompact, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn hook_output_pre_tool_use_rewrites_input() {
+        let output = HookOutput::modify_input(serde_json::json!({"command": "make --dry-run"}));
+        let json = hook_output_json(&HookEvent::PreToolUse, &output);
+        assert_eq!(json["continue"], true);
+        assert_eq!(json["hookSpecificOutput"]["hookEventName"], "PreToolUse");
+        assert_eq!(json["hookSpecificOutput"]["permissionDecision"], "allow");
+        assert_eq!(json["hookSpecificOutput"]["updatedInput"]["command"], "make --dry-run");
+    }
+
+    #[test]
+    fn hook_output_pre_tool_use_block_denies_only_the_call() {
+        let json = hook_output_json(&HookEvent::PreToolUse, &HookOutput::block("no"));
+        assert_eq!(json["continue"], true);
+        assert_eq!(json["hookSpecificOutput"]["permissionDecision"], "deny");
+        assert_eq!(json["hookSpecificOutput"]["permissionDecisionReason"], "no");
+    }
+
+    #[test]
+    fn hook_output_permission_decision_is_pre_tool_use_only() {
+        for event in [
+            HookEvent::Notification,
+            HookEvent::PreCompact,
+            HookEvent::SessionStart,
+            HookEvent::SessionEnd,
+        ] {
+            let json = hook_output_json(&event, &HookOutput::approve());
+            assert!(json.get("hookSpecificOutput").is_none(), "{event}");
+            assert_eq!(json["continue"], true);
+        }
+    }
+
+    #[test]
+    fn hook_output_ignore_leaves_the_permission_decision_open() {
+        let json = hook_output_json(&HookEvent::PreToolUse, &HookOutput::ignore());
+        assert!(json["hookSpecificOutput"].get("permissionDecision").is_none());
+        assert!(json["hookSpecificOutput"]
+            .get("permissionDecisionReason")
+            .is_none());
+    }
+
+    #[test]
+    fn hook_output_additional_context() {
+        let output = HookOutput::default().with_additional_context("lint passed");
+        let json = hook_output_json(&HookEvent::PostToolUse, &output);
+        assert_eq!(json["hookSpecificOutput"]["hookEventName"], "PostToolUse");
+        assert_eq!(json["hookSpecificOutput"]["additionalContext"], "lint passed");
+
+        // Ignored for events that don't support it.
+        let json = hook_output_json(&HookEvent::Notification, &output);
+        assert!(json.get("hookSpecificOutput").is_none());
+    }
+
+    #[test]
+    fn hook_output_stop_forces_continuation() {
+        let output = HookOutput::force_continue("tests are still failing");
+        let json = hook_output_json(&HookEvent::Stop, &output);
+        assert_eq!(json["continue"], true);
+        assert_eq!(json["decision"], "block");
+        assert_eq!(json["reason"], "tests are still failing");
+        assert!(json.get("hookSpecificOutput").is_none());
+    }
+
+    #[test]
+    fn hook_output_common_fields() {
+        let output = HookOutput::stop("budget exhausted")
+            .with_system_message("stopping early")
+            .with_suppress_output();
+        let json = hook_output_json(&HookEvent::SessionEnd, &output);
+        assert_eq!(json["continue"], false);
+        assert_eq!(json["stopReason"], "budget exhausted");
+        assert_eq!(json["systemMessage"], "stopping early");
+        assert_eq!(json["suppressOutput"], true);
+    }
+
+    #[tokio::test]
+    async fn forward_input_stream_stops_on_cancel() {
+        let (tx, _rx) = mpsc::channel(8);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let pending = tokio_stream::pending::<UserInput>();
+        forward_input_stream(pending, TransportWriter::new(tx), cancel)
+            .await
+            .unwrap();
+    }
+
+    fn hook(event: HookEvent, matcher: HookMatcher, output: HookOutput) -> HookDefinition {
+        HookDefinition::new(
+            event,
+            matcher,
+            hook_callback(move |_| {
+                let output = output.clone();
+                async move { output }
+            }),
+        )
+    }
+
+    fn compile(hooks: &[HookDefinition]) -> Vec<CompiledMatcher> {
+        hooks.iter().map(|h| h.matcher.compile().unwrap()).collect()
+    }
+
+    fn bash_input(command: &str) -> Value {
+        serde_json::json!({"tool_name": "Bash", "tool_input": {"command": command}})
+    }
+
+    #[tokio::test]
+    async fn hook_callback_skips_non_matching_tool_uses() {
+        let hooks = vec![hook(
+            HookEvent::PreToolUse,
+            HookMatcher::tool("Bash").with_input_prefix("command", "git push"),
+            HookOutput::block("blocked"),
+        )];
+        let matchers = compile(&hooks);
+        let (tx, _rx) = mpsc::channel(8);
+        let request = |tool: &str, command: &str| {
+            serde_json::json!({
+                "callback_id": "hook_PreToolUse",
+                "input": {"tool_name": tool, "tool_input": {"command": command}}
+            })
+        };
+
+        let skipped = handle_hook_callback(&request("Bash", "git status"), &hooks, &matchers, &tx, None, None).await;
+        assert_eq!(skipped, serde_json::json!({"continue": true}));
+
+        let skipped = handle_hook_callback(&request("Read", "git push"), &hooks, &matchers, &tx, None, None).await;
+        assert_eq!(skipped, serde_json::json!({"continue": true}));
+
+        let blocked = handle_hook_callback(&request("Bash", "git push -f"), &hooks, &matchers, &tx, None, None).await;
+        assert_eq!(blocked["hookSpecificOutput"]["permissionDecision"], "deny");
+    }
+
+    #[tokio::test]
+    async fn hooks_chain_input_and_first_block_wins() {
+        // Each hook appends to the command it sees.
+        let append = |suffix: &'static str| {
+            HookDefinition::new(
+                HookEvent::PreToolUse,
+                HookMatcher::tool("Bash"),
+                hook_callback(move |input| async move {
+                    let HookInput::PreToolUse(pre) = input else {
+                        return HookOutput::default();
+                    };
+                    let command = pre.tool_input["command"].as_str().unwrap_or("");
+                    HookOutput::approve()
+                        .with_updated_input(serde_json::json!({"command": format!("{command}{suffix}")}))
+                        .with_additional_context(suffix)
+                }),
+            )
+        };
+        let hooks = vec![append(" -a"), append(" -b")];
+        let HookRun { output, errors, .. } =
+            run_hooks(&HookEvent::PreToolUse, bash_input("ls"), &hooks, &compile(&hooks)).await;
+        assert!(errors.is_empty());
+        assert_eq!(output.decision, Some(HookDecision::Approve));
+        assert_eq!(output.updated_input, Some(serde_json::json!({"command": "ls -a -b"})));
+        assert_eq!(output.additional_context.as_deref(), Some(" -a\n -b"));
+
+        let hooks = vec![
+            hook(HookEvent::PreToolUse, HookMatcher::default(), HookOutput::approve()),
+            hook(HookEvent::PreToolUse, HookMatcher::default(), HookOutput::block("first")),
+            hook(HookEvent::PreToolUse, HookMatcher::default(), HookOutput::block("second")),
+        ];
+        let HookRun { output, .. } =
+            run_hooks(&HookEvent::PreToolUse, bash_input("ls"), &hooks, &compile(&hooks)).await;
+        assert_eq!(output.decision, Some(HookDecision::Block));
+        assert_eq!(output.reason.as_deref(), Some("first"));
+    }
+
+    #[tokio::test]
+    async fn ignore_is_no_decision() {
+        let hooks = vec![
+            hook(HookEvent::PreToolUse, HookMatcher::default(), HookOutput::ignore()),
+            hook(HookEvent::PreToolUse, HookMatcher::default(), HookOutput::approve()),
+        ];
+        let HookRun { output, decided_by, .. } =
+            run_hooks(&HookEvent::PreToolUse, bash_input("ls"), &hooks, &compile(&hooks)).await;
+        assert_eq!(output.decision, Some(HookDecision::Approve));
+        assert_eq!(decided_by, Some(1));
+    }
+
+    #[tokio::test]
+    async fn hook_timeouts_follow_failure_policy() {
+        let hang = || {
+            HookDefinition::new(
+                HookEvent::PreToolUse,
+                HookMatcher::default(),
+                hook_callback(|_| std::future::pending()),
+            )
+            .with_timeout(Duration::from_millis(20))
+        };
+
+        let hooks = vec![hang()];
+        let HookRun { output, errors, .. } =
+            run_hooks(&HookEvent::PreToolUse, bash_input("ls"), &hooks, &compile(&hooks)).await;
+        assert_eq!(output.decision, None);
+        assert!(matches!(errors.as_slice(), [Error::Hook(msg)] if msg.contains("timed out")));
+
+        let hooks = vec![hang().fail_closed()];
+        let HookRun { output, errors, .. } =
+            run_hooks(&HookEvent::PreToolUse, bash_input("ls"), &hooks, &compile(&hooks)).await;
+        assert_eq!(output.decision, Some(HookDecision::Block));
+        assert!(matches!(errors.as_slice(), [Error::Hook(msg)] if msg.contains("timed out")));
+    }
+
+    #[tokio::test]
+    async fn hook_failures_are_reported_on_the_message_stream() {
+        // Malformed input: `stop_hook_active` must be a bool.
+        let request = serde_json::json!({
+            "callback_id": "hook_Stop",
+            "input": {"stop_hook_active": "yes"}
+        });
+        let (tx, mut rx) = mpsc::channel(8);
+
+        let hooks = vec![hook(HookEvent::Stop, HookMatcher::default(), HookOutput::stop("done"))];
+        let matchers = compile(&hooks);
+        let response = handle_hook_callback(&request, &hooks, &matchers, &tx, None, None).await;
+        // Fail-open: the session carries on, but the failure is still reported.
+        assert_eq!(response, serde_json::json!({"continue": true}));
+        assert!(matches!(rx.recv().await, Some(Err(Error::Hook(_)))));
+
+        let hooks = vec![
+            hook(HookEvent::Stop, HookMatcher::default(), HookOutput::stop("done")).fail_closed(),
+        ];
+        let matchers = compile(&hooks);
+        let response = handle_hook_callback(&request, &hooks, &matchers, &tx, None, None).await;
+        assert_eq!(response["decision"], "block");
+        assert!(matches!(rx.recv().await, Some(Err(Error::Hook(_)))));
+
+        let request = serde_json::json!({"callback_id": "hook_Notification", "input": {}});
+        handle_hook_callback(&request, &hooks, &matchers, &tx, None, None).await;
+        assert!(matches!(rx.recv().await, Some(Err(Error::Hook(_)))));
+    }
+
+    #[test]
+    fn hooks_config_groups_by_event() {
+        let hooks = vec![
+            hook(HookEvent::PreToolUse, HookMatcher::tool("Edit|Write"), HookOutput::default()),
+            hook(HookEvent::Stop, HookMatcher::default(), HookOutput::default()),
+            hook(HookEvent::PostToolUse, HookMatcher::tool("Bash"), HookOutput::default()),
+            hook(HookEvent::PostToolUse, HookMatcher::tool("Read"), HookOutput::default()),
+            hook(HookEvent::PreToolUse, HookMatcher::default(), HookOutput::default()),
+        ];
+        assert_eq!(
+            hooks_config(&hooks),
+            serde_json::json!({
+                "PreToolUse": [{"hookCallbackIds": ["hook_PreToolUse"]}],
+                "PostToolUse": [{
+                    "matcher": "^(?:Bash)$|^(?:Read)$",
+                    "hookCallbackIds": ["hook_PostToolUse"]
+                }],
+                "Stop": [{"hookCallbackIds": ["hook_Stop"]}]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn can_use_tool_passes_suggestions_and_returns_updates() {
+        let callback = crate::types::permissions::permission_callback(|input| async move {
+            assert_eq!(input.tool_use_id.as_deref(), Some("toolu_1"));
+            let suggestion = input.permission_suggestions[0].clone();
+            PermissionResult::allow()
+                .with_updated_input(serde_json::json!({"command": "ls -la"}))
+                .with_permission_update(suggestion)
+        });
+        let request = serde_json::json!({
+            "subtype": "can_use_tool",
+            "tool_name": "Bash",
+            "input": {"command": "ls"},
+            "tool_use_id": "toolu_1",
+            "permission_suggestions": [
+                {"type": "someFutureUpdate"},
+                {
+                    "type": "addRules",
+                    "rules": [{"toolName": "Bash", "ruleContent": "ls:*"}],
+                    "behavior": "allow",
+                    "destination": "session"
+                }
+            ]
+        });
+
+        let response = handle_can_use_tool(&request, &Some(callback), None, None).await;
+        assert_eq!(
+            response,
+            serde_json::json!({
+                "behavior": "allow",
+                "updatedInput": {"command": "ls -la"},
+                "updatedPermissions": [{
+                    "type": "addRules",
+                    "rules": [{"toolName": "Bash", "ruleContent": "ls:*"}],
+                    "behavior": "allow",
+                    "destination": "session"
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn permission_result_json_shapes() {
+        let input = serde_json::json!({"file_path": "a.rs"});
+        assert_eq!(
+            permission_result_json(PermissionResult::allow(), input.clone()),
+            serde_json::json!({"behavior": "allow", "updatedInput": {"file_path": "a.rs"}})
+        );
+        assert_eq!(
+            permission_result_json(PermissionResult::deny("no"), input.clone()),
+            serde_json::json!({"behavior": "deny", "message": "no"})
+        );
+        assert_eq!(
+            permission_result_json(PermissionResult::deny_and_interrupt("stop"), input),
+            serde_json::json!({"behavior": "deny", "message": "stop", "interrupt": true})
+        );
+    }
+
+    #[tokio::test]
+    async fn permission_decisions_are_audited() {
+        let records = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let audit_log = AuditLog::new({
+            let records = records.clone();
+            move |r: &AuditRecord| {
+                records.lock().unwrap().push(r.clone());
+                Ok(())
+            }
+        });
+
+        let policy = crate::permissions::PermissionPolicy::from_toml(r#"deny = ["Bash(rm:*)"]"#)
+            .unwrap()
+            .into_callback();
+        let request = serde_json::json!({
+            "tool_name": "Bash",
+            "input": {"command": "rm -rf target", "api_token": "t0k"},
+            "tool_use_id": "toolu_1"
+        });
+        handle_can_use_tool(&request, &Some(policy), Some(&audit_log), Some("sess_1")).await;
+
+        let hooks = vec![
+            hook(HookEvent::PreToolUse, HookMatcher::default(), HookOutput::default()),
+            hook(HookEvent::PreToolUse, HookMatcher::default(), HookOutput::approve()),
+        ];
+        let request = serde_json::json!({
+            "callback_id": "hook_PreToolUse",
+            "input": {"session_id": "sess_2", "tool_name": "Read", "tool_input": {"file_path": "a"}}
+        });
+        let (tx, _rx) = mpsc::channel(8);
+        handle_hook_callback(&request, &hooks, &compile(&hooks), &tx, Some(&audit_log), None).await;
+
+        let records = records.lock().unwrap();
+        let [policy, hook] = records.as_slice() else {
+            panic!("expected two records, got {records:?}");
+        };
+        assert_eq!(policy.source, AuditSource::CanUseTool);
+        assert_eq!(policy.session_id.as_deref(), Some("sess_1"));
+        assert_eq!(policy.tool_use_id.as_deref(), Some("toolu_1"));
+        assert_eq!(policy.decision, AuditDecision::Deny);
+        assert_eq!(policy.decided_by, "permission policy rule `Bash(rm:*)`");
+        assert_eq!(policy.input["api_token"], "[REDACTED]");
+
+        assert_eq!(hook.source, AuditSource::PreToolUseHook);
+        assert_eq!(hook.session_id.as_deref(), Some("sess_2"));
+        assert_eq!(hook.decision, AuditDecision::Allow);
+        assert_eq!(hook.decided_by, "PreToolUse hook #1");
+    }
+
+    #[tokio::test]
+    async fn pending_permission_requests_do_not_block_the_router() {
+        use crate::permissions::ApprovalBroker;
+        use crate::transport::fake::fake_transport;
+
+        let broker = ApprovalBroker::new();
+        let (transport, mut cli) = fake_transport();
+        let mut q = Query::new(
+            Box::new(transport),
+            vec![],
+            Some(broker.callback()),
+            None,
+            Some(Duration::from_secs(5)),
+        );
+        let (rx, _) = tokio::join!(q.connect(), cli.answer_control_request());
+        let mut rx = rx.unwrap();
+
+        let permission_request = |id: &str| {
+            serde_json::json!({
+                "type": "control_request",
+                "request_id": id,
+                "request": {
+                    "subtype": "can_use_tool",
+                    "tool_name": "Bash",
+                    "input": {"command": "ls"}
+                }
+            })
+        };
+        cli.send(permission_request("perm_1")).await;
+        cli.send(permission_request("perm_2")).await;
+        cli.send(serde_json::json!({
+            "type": "assistant",
+            "message": {
+                "role": "assistant",
+                "model": "m",
+                "content": [{"type": "text", "text": "hi"}]
+            }
+        }))
+        .await;
+
+        // Messages and control responses are routed while both wait for a human.
+        let message = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap();
+        assert_eq!(message.unwrap().unwrap().text().as_deref(), Some("hi"));
+        let (interrupted, request) = tokio::join!(q.interrupt(), cli.answer_control_request());
+        assert!(interrupted.is_ok());
+        assert_eq!(request["request"]["subtype"], "interrupt");
+        assert_eq!(broker.pending().len(), 2);
+
+        for (id, _) in broker.pending() {
+            broker.approve(id);
+        }
+        let mut answered = vec![
+            cli.recv().await["response"]["request_id"].clone(),
+            cli.recv().await["response"]["request_id"].clone(),
+        ];
+        answered.sort_by_key(|id| id.to_string());
+        assert_eq!(answered, ["perm_1", "perm_2"]);
+    }
+}
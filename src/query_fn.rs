@@ -1,9 +1,10 @@
 use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
 
 use crate::error::{Error, Result};
 use crate::query::Query;
 use crate::transport::subprocess::SubprocessTransport;
-use crate::types::messages::Message;
+use crate::types::messages::{Message, UserInput};
 use crate::types::options::ClaudeAgentOptions;
 
 /// Execute a one-shot query against the Claude CLI and return a stream of messages.
@@ -54,12 +55,13 @@ pub async fn query(
         options.can_use_tool,
         None, // MCP handler wired through client, not one-shot query
         options.control_timeout,
-    );
+    )
+    .with_audit_log(options.audit_log);
 
     let rx = q.connect().await?;
 
     // Send the prompt.
-    q.send_message(prompt, None).await?;
+    q.send_message(prompt.into(), None).await?;
 
     // Keep Query alive in a background task until the consumer channel closes.
     // When rx is dropped by the consumer, consumer_tx.send() fails and the
@@ -71,6 +73,59 @@ pub async fn query(
     Ok(ReceiverStream::new(rx))
 }
 
+/// Execute a one-shot query whose user messages come from a stream.
+///
+/// Messages are forwarded to the CLI as they arrive, so follow-up
+/// instructions can be queued while earlier ones are still being worked on.
+/// When `input` ends, the CLI's stdin is closed so it can finish; the
+/// returned stream then ends once the CLI exits. A failure to write the
+/// input is reported as an error on the returned stream.
+pub async fn query_stream<S>(
+    input: S,
+    options: ClaudeAgentOptions,
+) -> Result<ReceiverStream<Result<Message>>>
+where
+    S: Stream<Item = UserInput> + Send + 'static,
+{
+    let cli_path = options.resolve_cli_path()?;
+    let transport = SubprocessTransport::new(cli_path, &options);
+    let q = Query::new(
+        Box::new(transport),
+        options.hooks,
+        options.can_use_tool,
+        None,
+        options.control_timeout,
+    )
+    .with_audit_log(options.audit_log);
+    stream_query(q, input).await
+}
+
+/// Connect `q`, forward `input` to it and close stdin once `input` ends.
+async fn stream_query<S>(mut q: Query, input: S) -> Result<ReceiverStream<Result<Message>>>
+where
+    S: Stream<Item = UserInput> + Send + 'static,
+{
+    let rx = q.connect().await?;
+    let forwarder = q.stream_input(input)?;
+
+    tokio::spawn(async move {
+        let forwarded = match forwarder.await {
+            Ok(result) => result,
+            Err(e) => Err(Error::Process(format!("input stream task failed: {e}"))),
+        };
+        let result = match forwarded {
+            Ok(()) if !q.is_closed() => q.end_input().await,
+            other => other,
+        };
+        if let Err(e) = result {
+            q.report_error(e).await;
+        }
+        q.closed().await;
+    });
+
+    Ok(ReceiverStream::new(rx))
+}
+
 /// Execute a query and collect all messages until the result.
 ///
 /// Returns the full list of messages including the final ResultMessage.
@@ -113,3 +168,49 @@ pub async fn query_text(
 
     Ok(text)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::fake::{fake_transport, FakeCli};
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn query_stream_closes_stdin_when_input_ends() {
+        let (transport, mut cli) = fake_transport();
+        let q = Query::new(Box::new(transport), vec![], None, None, None);
+        let input = tokio_stream::iter(vec![UserInput::new("hello")]);
+
+        let (stream, _) = tokio::join!(stream_query(q, input), cli.answer_control_request());
+        let mut stream = stream.unwrap();
+
+        assert_eq!(cli.recv().await["message"]["content"], "hello");
+        cli.stdin_closed().await;
+
+        // The CLI exits after EOF; the message stream ends with it.
+        drop(cli);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn query_stream_reports_input_write_errors() {
+        let (transport, mut cli) = fake_transport();
+        let q = Query::new(Box::new(transport), vec![], None, None, None);
+        let (input_tx, input_rx) = tokio::sync::mpsc::channel(1);
+        let input = ReceiverStream::new(input_rx);
+
+        let (stream, _) = tokio::join!(stream_query(q, input), cli.answer_control_request());
+        let mut stream = stream.unwrap();
+
+        // The CLI stops reading stdin.
+        let FakeCli { stdout, stdin, .. } = cli;
+        drop(stdin);
+        input_tx.send(UserInput::new("lost")).await.unwrap();
+
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(Error::TransportClosed))
+        ));
+        drop(stdout);
+    }
+}
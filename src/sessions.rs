@@ -0,0 +1,350 @@
+//! Past sessions, read from the CLI's local JSONL transcripts.
+//!
+//! The CLI writes one transcript per session to
+//! `<config dir>/projects/<encoded cwd>/<session id>.jsonl`, where the config
+//! dir is `$CLAUDE_CONFIG_DIR` or `~/.claude`.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::message_parser::parse_message;
+use crate::types::messages::Message;
+
+/// Metadata of a stored session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionInfo {
+    pub session_id: String,
+    /// The transcript file.
+    pub path: PathBuf,
+    /// Working directory of the session.
+    pub cwd: Option<PathBuf>,
+    /// First thing the user typed.
+    pub first_prompt: Option<String>,
+    /// Summary the CLI generated for the session, if any.
+    pub summary: Option<String>,
+    /// RFC 3339 timestamp of the first message, as written by the CLI.
+    pub started_at: Option<String>,
+    /// RFC 3339 timestamp of the last message.
+    pub updated_at: Option<String>,
+    /// Sum of the per-message costs recorded in the transcript, if any.
+    pub cost_usd: Option<f64>,
+    /// Number of user and assistant messages.
+    pub message_count: usize,
+    /// When the transcript was last written.
+    pub modified: SystemTime,
+}
+
+/// Reads session transcripts from a CLI config directory.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    projects_dir: PathBuf,
+}
+
+impl SessionStore {
+    /// The store of the current user: `$CLAUDE_CONFIG_DIR`, else `~/.claude`.
+    pub fn new() -> Result<Self> {
+        let config_dir = match std::env::var_os("CLAUDE_CONFIG_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".claude"))
+                .ok_or_else(|| {
+                    Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        "cannot locate the Claude config dir: HOME is not set",
+                    ))
+                })?,
+        };
+        Ok(Self::with_config_dir(config_dir))
+    }
+
+    /// The store under an explicit config dir.
+    #[must_use]
+    pub fn with_config_dir(config_dir: impl AsRef<Path>) -> Self {
+        Self {
+            projects_dir: config_dir.as_ref().join("projects"),
+        }
+    }
+
+    /// All sessions, most recently updated first. Transcripts that can't be
+    /// read are skipped with a warning.
+    pub fn list(&self) -> Result<Vec<SessionInfo>> {
+        let mut sessions = Vec::new();
+        for project in read_dir_or_empty(&self.projects_dir)? {
+            if project.is_dir() {
+                sessions.extend(self.list_dir(&project)?);
+            }
+        }
+        sort_newest_first(&mut sessions);
+        Ok(sessions)
+    }
+
+    /// Sessions started in `cwd`, most recently updated first.
+    pub fn list_for_project(&self, cwd: impl AsRef<Path>) -> Result<Vec<SessionInfo>> {
+        let mut sessions = self.list_dir(&self.projects_dir.join(encode_project(cwd.as_ref())))?;
+        sort_newest_first(&mut sessions);
+        Ok(sessions)
+    }
+
+    /// Metadata of session `session_id`, if its transcript exists.
+    pub fn get(&self, session_id: &str) -> Result<Option<SessionInfo>> {
+        self.transcript_path(session_id)?
+            .map(|path| read_info(&path))
+            .transpose()
+    }
+
+    /// The messages of session `session_id`, in transcript order.
+    ///
+    /// Lines that don't parse (e.g. a partially written last line) are skipped.
+    pub fn load(&self, session_id: &str) -> Result<Vec<Message>> {
+        let path = self.transcript_path(session_id)?.ok_or_else(|| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no transcript for session {session_id}"),
+            ))
+        })?;
+        load_transcript(&path)
+    }
+
+    fn list_dir(&self, dir: &Path) -> Result<Vec<SessionInfo>> {
+        Ok(read_dir_or_empty(dir)?
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .filter_map(|path| match read_info(&path) {
+                Ok(info) => Some(info),
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), "skipping unreadable transcript: {e}");
+                    None
+                }
+            })
+            .collect())
+    }
+
+    fn transcript_path(&self, session_id: &str) -> Result<Option<PathBuf>> {
+        if session_id.is_empty() || session_id.contains(['/', '\\']) || session_id.starts_with('.')
+        {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid session id {session_id:?}"),
+            )));
+        }
+        let file = format!("{session_id}.jsonl");
+        Ok(read_dir_or_empty(&self.projects_dir)?
+            .into_iter()
+            .map(|project| project.join(&file))
+            .find(|path| path.is_file()))
+    }
+}
+
+/// Read the messages of a transcript file.
+pub fn load_transcript(path: impl AsRef<Path>) -> Result<Vec<Message>> {
+    Ok(transcript_lines(path.as_ref())?
+        .filter_map(|raw| parse_message(raw).ok())
+        .collect())
+}
+
+/// Project directory name the CLI uses for `cwd`.
+fn encode_project(cwd: &Path) -> String {
+    cwd.to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+fn read_info(path: &Path) -> Result<SessionInfo> {
+    let mut info = SessionInfo {
+        session_id: path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        path: path.to_path_buf(),
+        cwd: None,
+        first_prompt: None,
+        summary: None,
+        started_at: None,
+        updated_at: None,
+        cost_usd: None,
+        message_count: 0,
+        modified: std::fs::metadata(path)?.modified()?,
+    };
+
+    for raw in transcript_lines(path)? {
+        let str_field = |name: &str| raw.get(name).and_then(Value::as_str);
+        match str_field("type") {
+            Some("summary") => {
+                info.summary = str_field("summary").map(String::from);
+                continue;
+            }
+            Some("user" | "assistant") => info.message_count += 1,
+            _ => {}
+        }
+        if info.cwd.is_none() {
+            info.cwd = str_field("cwd").map(PathBuf::from);
+        }
+        if let Some(timestamp) = str_field("timestamp") {
+            info.started_at.get_or_insert_with(|| timestamp.to_string());
+            info.updated_at = Some(timestamp.to_string());
+        }
+        if let Some(cost) = raw.get("costUSD").and_then(Value::as_f64) {
+            *info.cost_usd.get_or_insert(0.0) += cost;
+        }
+        if info.first_prompt.is_none() && str_field("type") == Some("user") {
+            info.first_prompt = prompt_text(&raw);
+        }
+    }
+    Ok(info)
+}
+
+/// Text the user typed, for user lines that are not tool results or
+/// CLI-injected (`isMeta`) messages.
+fn prompt_text(raw: &Value) -> Option<String> {
+    if raw.get("isMeta").and_then(Value::as_bool) == Some(true) {
+        return None;
+    }
+    let content = raw.get("message")?.get("content")?;
+    let text = match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter(|b| b.get("type").and_then(Value::as_str) == Some("text"))
+            .filter_map(|b| b.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => return None,
+    };
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// The JSON lines of a transcript, skipping ones that don't parse (including
+/// invalid UTF-8). Stops early if reading fails.
+fn transcript_lines(path: &Path) -> Result<impl Iterator<Item = Value>> {
+    let reader = BufReader::new(File::open(path)?);
+    let path = path.to_path_buf();
+    let read_path = path.clone();
+    Ok(reader
+        .split(b'\n')
+        .map_while(move |line| {
+            line.map_err(|e| {
+                tracing::warn!(path = %read_path.display(), "stopped reading transcript: {e}");
+            })
+            .ok()
+        })
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .filter_map(move |line| match serde_json::from_slice(&line) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                tracing::debug!(path = %path.display(), "skipping transcript line: {e}");
+                None
+            }
+        }))
+}
+
+fn read_dir_or_empty(dir: &Path) -> Result<Vec<PathBuf>> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => Ok(entries.filter_map(|e| e.ok().map(|e| e.path())).collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn sort_newest_first(sessions: &mut [SessionInfo]) {
+    sessions.sort_by(|a, b| (&b.updated_at, b.modified).cmp(&(&a.updated_at, a.modified)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APP_SESSION: &str = "0a6f3c2e-1111-4c1e-9d7a-000000000001";
+    const LIB_SESSION: &str = "5b1d9e40-2222-4f3a-8c11-000000000002";
+
+    fn store() -> SessionStore {
+        SessionStore::with_config_dir(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/claude-config"),
+        )
+    }
+
+    #[test]
+    fn lists_sessions_with_metadata() {
+        let sessions = store().list().unwrap();
+        assert_eq!(
+            sessions
+                .iter()
+                .map(|s| s.session_id.as_str())
+                .collect::<Vec<_>>(),
+            [LIB_SESSION, APP_SESSION]
+        );
+
+        let app = &sessions[1];
+        assert_eq!(app.cwd.as_deref(), Some(Path::new("/work/app")));
+        assert_eq!(
+            app.first_prompt.as_deref(),
+            Some("Fix the failing parser test\nIt started after the last merge.")
+        );
+        assert_eq!(app.summary.as_deref(), Some("Fix failing parser test"));
+        assert_eq!(app.started_at.as_deref(), Some("2025-10-01T09:00:00.000Z"));
+        assert_eq!(app.updated_at.as_deref(), Some("2025-10-01T09:00:12.500Z"));
+        assert!((app.cost_usd.unwrap() - 0.02).abs() < 1e-9);
+        // The truncated last line is not counted.
+        assert_eq!(app.message_count, 5);
+
+        let lib = &sessions[0];
+        assert_eq!(lib.first_prompt.as_deref(), Some("Summarize src/lib.rs"));
+        assert_eq!(lib.cost_usd, None);
+
+        let only_lib = store().list_for_project("/work/lib").unwrap();
+        assert_eq!(only_lib.len(), 1);
+        assert_eq!(only_lib[0].session_id, LIB_SESSION);
+        assert!(store().list_for_project("/elsewhere").unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_bad_lines_and_unreadable_transcripts() {
+        let config = tempfile::tempdir().unwrap();
+        let project = config.path().join("projects/-work-app");
+        std::fs::create_dir_all(&project).unwrap();
+        let mut transcript = br#"{"type":"user","message":{"content":"one"}}"#.to_vec();
+        transcript.extend_from_slice(b"\n{\"type\":\"user\",\"bad\":\"\xff\"}\n");
+        transcript.extend_from_slice(br#"{"type":"assistant","message":{"content":[]}}"#);
+        std::fs::write(project.join("s1.jsonl"), transcript).unwrap();
+        std::os::unix::fs::symlink(project.join("missing"), project.join("s2.jsonl")).unwrap();
+
+        let store = SessionStore::with_config_dir(config.path());
+        let sessions = store.list().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].message_count, 2);
+        assert_eq!(store.load("s1").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn loads_messages() {
+        let messages = store().load(APP_SESSION).unwrap();
+        let kinds: Vec<_> = messages
+            .iter()
+            .map(|m| match m {
+                Message::User { .. } => "user",
+                Message::Assistant { .. } => "assistant",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            ["other", "user", "user", "assistant", "user", "assistant"]
+        );
+        assert_eq!(
+            messages[5].text().as_deref(),
+            Some("The parser test expects a trailing newline; fixed.")
+        );
+
+        assert!(store().get(LIB_SESSION).unwrap().is_some());
+        assert!(store().get("missing").unwrap().is_none());
+        assert!(store().load("missing").is_err());
+        assert!(store().load("../etc/passwd").is_err());
+    }
+}
@@ -0,0 +1,511 @@
+//! Per-file before/after content of the agent's edits, as unified diffs.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::types::content::ContentBlock;
+use crate::types::hooks::{
+    hook_callback, HookDefinition, HookEvent, HookInput, HookMatcher, HookOutput, PostToolUseInput,
+};
+use crate::types::messages::{Message, UserContent};
+use crate::types::tools::{BuiltinToolInput, EditOperation};
+
+/// A file's content before and after the agent changed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// Relative to the tracker's base directory when inside it.
+    pub path: PathBuf,
+    /// `None` if the file didn't exist.
+    pub before: Option<String>,
+    /// `None` if the file no longer exists.
+    pub after: Option<String>,
+}
+
+impl FileChange {
+    pub fn is_created(&self) -> bool {
+        self.before.is_none() && self.after.is_some()
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        self.before == self.after
+    }
+
+    /// The change as a unified diff with `a/` and `b/` headers.
+    pub fn unified_diff(&self) -> String {
+        let path = self.path.display().to_string();
+        let path = path.trim_start_matches('/');
+        let header = |side: &str, content: &Option<String>| match content {
+            Some(_) => format!("{side}/{path}"),
+            None => "/dev/null".to_string(),
+        };
+        similar::TextDiff::from_lines(
+            self.before.as_deref().unwrap_or(""),
+            self.after.as_deref().unwrap_or(""),
+        )
+        .unified_diff()
+        .context_radius(3)
+        .header(&header("a", &self.before), &header("b", &self.after))
+        .to_string()
+    }
+}
+
+/// Concatenate the diffs of `changes` into one patch.
+pub fn combined_patch(changes: &[FileChange]) -> String {
+    changes
+        .iter()
+        .filter(|c| !c.is_unchanged())
+        .map(FileChange::unified_diff)
+        .collect()
+}
+
+struct PendingEdit {
+    input: BuiltinToolInput,
+    path: PathBuf,
+    /// File content when the tool use was seen, if not already tracked.
+    snapshot: Option<Option<String>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    base_dir: Option<PathBuf>,
+    /// The session's working directory, from the init message or a hook.
+    cwd: Option<PathBuf>,
+    pending: HashMap<String, PendingEdit>,
+    /// Tool uses already recorded from a PostToolUse hook.
+    applied: HashSet<String>,
+    session: BTreeMap<PathBuf, FileChange>,
+    turn: BTreeMap<PathBuf, FileChange>,
+}
+
+/// Records what Edit, MultiEdit and Write tool uses did to each file.
+///
+/// Feed it the message stream with [`observe`](Self::observe). Each file is
+/// read when its first tool use is seen, and edits are replayed from the
+/// tool inputs once their results arrive.
+///
+/// On its own this is best effort: nothing stops the CLI from applying an
+/// edit before the caller observes its tool use, in which case the file is
+/// read after the change and the edit goes unrecorded. Install
+/// [`hook`](Self::hook) as well for exact results; it uses the original
+/// content the CLI reports in PostToolUse.
+///
+/// Relative tool paths resolve against the base directory, or else the
+/// session's `cwd` once the init message or a hook input has reported it.
+///
+/// Cloning gives another handle to the same tracker.
+#[derive(Clone, Default)]
+pub struct FileChangeTracker {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl FileChangeTracker {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve relative tool paths against `dir` and report paths relative to it.
+    #[must_use]
+    pub fn with_base_dir(self, dir: impl Into<PathBuf>) -> Self {
+        self.lock().base_dir = Some(dir.into());
+        self
+    }
+
+    /// Record `message`. At the end of a turn (a `Result` message), returns
+    /// the files changed during that turn.
+    pub fn observe(&self, message: &Message) -> Vec<FileChange> {
+        let mut inner = self.lock();
+        match message {
+            Message::Assistant { message } => {
+                for block in &message.content {
+                    let ContentBlock::ToolUse { id, .. } = block else {
+                        continue;
+                    };
+                    let Some(input) = block.tool_input().filter(is_file_edit) else {
+                        continue;
+                    };
+                    if inner.applied.contains(id) {
+                        continue;
+                    }
+                    let path = inner.key(input.path().unwrap_or_default());
+                    let snapshot = match inner.session.contains_key(&path) {
+                        true => None,
+                        false => Some(read_file(&inner.resolve(&path))),
+                    };
+                    inner.pending.insert(
+                        id.clone(),
+                        PendingEdit {
+                            input,
+                            path,
+                            snapshot,
+                        },
+                    );
+                }
+                Vec::new()
+            }
+            Message::User { message } => {
+                let UserContent::Blocks(blocks) = &message.content else {
+                    return Vec::new();
+                };
+                for block in blocks {
+                    let ContentBlock::ToolResult {
+                        tool_use_id,
+                        is_error,
+                        ..
+                    } = block
+                    else {
+                        continue;
+                    };
+                    let pending = inner.pending.remove(tool_use_id);
+                    if inner.applied.remove(tool_use_id) || *is_error {
+                        continue;
+                    }
+                    if let Some(edit) = pending {
+                        inner.apply(edit.path, edit.snapshot, &edit.input);
+                    }
+                }
+                Vec::new()
+            }
+            Message::System { subtype, data } if subtype == "init" => {
+                if let Some(cwd) = data.get("cwd").and_then(|v| v.as_str()) {
+                    inner.cwd = Some(cwd.into());
+                }
+                Vec::new()
+            }
+            Message::Result { .. } => std::mem::take(&mut inner.turn)
+                .into_values()
+                .filter(|c| !c.is_unchanged())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Record a PostToolUse hook input.
+    pub fn observe_post_tool_use(&self, input: &PostToolUseInput) {
+        let typed = input.typed_input();
+        if !is_file_edit(&typed) {
+            return;
+        }
+        let mut inner = self.lock();
+        if let Some(cwd) = &input.base.cwd {
+            inner.cwd = Some(cwd.into());
+        }
+        let pending = input
+            .base
+            .tool_use_id
+            .as_ref()
+            .and_then(|id| inner.pending.remove(id));
+        let path = match &pending {
+            Some(edit) => edit.path.clone(),
+            None => inner.key(typed.path().unwrap_or_default()),
+        };
+        // `null` means the file was created.
+        let original = ["originalFile", "originalFileContents"]
+            .iter()
+            .find_map(|key| input.tool_output.get(key))
+            .map(|v| v.as_str().map(String::from));
+        let before = original.or_else(|| pending.and_then(|edit| edit.snapshot));
+        inner.apply(path, before, &typed);
+        if let Some(id) = &input.base.tool_use_id {
+            inner.applied.insert(id.clone());
+        }
+    }
+
+    /// A PostToolUse hook feeding this tracker; add it to `ClaudeAgentOptions::hooks`.
+    pub fn hook(&self) -> HookDefinition {
+        let tracker = self.clone();
+        HookDefinition::new(
+            HookEvent::PostToolUse,
+            HookMatcher::tool("Edit|MultiEdit|Write"),
+            hook_callback(move |input| {
+                if let HookInput::PostToolUse(input) = &input {
+                    tracker.observe_post_tool_use(input);
+                }
+                async { HookOutput::default() }
+            }),
+        )
+    }
+
+    /// Files changed since the tracker was created, by path.
+    pub fn changes(&self) -> Vec<FileChange> {
+        self.lock()
+            .session
+            .values()
+            .filter(|c| !c.is_unchanged())
+            .cloned()
+            .collect()
+    }
+
+    /// All changes since the tracker was created, as one patch.
+    pub fn patch(&self) -> String {
+        combined_patch(&self.changes())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Inner {
+    fn key(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        match &self.base_dir {
+            Some(base) => path.strip_prefix(base).unwrap_or(path).to_path_buf(),
+            None => path.to_path_buf(),
+        }
+    }
+
+    fn resolve(&self, key: &Path) -> PathBuf {
+        match self.base_dir.as_ref().or(self.cwd.as_ref()) {
+            Some(base) => base.join(key),
+            None => key.to_path_buf(),
+        }
+    }
+
+    /// Apply `input` on top of what we know of `path`. `before` is the
+    /// content of a file not seen yet, if known; otherwise it is read from disk.
+    fn apply(&mut self, path: PathBuf, before: Option<Option<String>>, input: &BuiltinToolInput) {
+        let current = match self.session.get(&path) {
+            Some(change) => change.after.clone(),
+            None => before.unwrap_or_else(|| read_file(&self.resolve(&path))),
+        };
+        // The tool succeeded, so if replaying it fails our view is stale:
+        // take what is on disk.
+        let after = replay(input, current.as_deref()).or_else(|| read_file(&self.resolve(&path)));
+
+        for changes in [&mut self.session, &mut self.turn] {
+            changes
+                .entry(path.clone())
+                .or_insert_with(|| FileChange {
+                    path: path.clone(),
+                    before: current.clone(),
+                    after: None,
+                })
+                .after = after.clone();
+        }
+    }
+}
+
+fn is_file_edit(input: &BuiltinToolInput) -> bool {
+    matches!(
+        input,
+        BuiltinToolInput::Edit(_) | BuiltinToolInput::MultiEdit(_) | BuiltinToolInput::Write(_)
+    )
+}
+
+/// Content after applying `input` to `current`; `None` if an edit doesn't apply.
+fn replay(input: &BuiltinToolInput, current: Option<&str>) -> Option<String> {
+    match input {
+        BuiltinToolInput::Write(write) => Some(write.content.clone()),
+        BuiltinToolInput::Edit(edit) => apply_edit(
+            current.unwrap_or(""),
+            &EditOperation {
+                old_string: edit.old_string.clone(),
+                new_string: edit.new_string.clone(),
+                replace_all: edit.replace_all,
+            },
+        ),
+        BuiltinToolInput::MultiEdit(multi) => multi
+            .edits
+            .iter()
+            .try_fold(current.unwrap_or("").to_string(), |content, edit| {
+                apply_edit(&content, edit)
+            }),
+        _ => None,
+    }
+}
+
+fn apply_edit(content: &str, edit: &EditOperation) -> Option<String> {
+    if edit.old_string.is_empty() {
+        // An empty `old_string` creates the file.
+        return content.is_empty().then(|| edit.new_string.clone());
+    }
+    if !content.contains(&edit.old_string) {
+        return None;
+    }
+    Some(match edit.replace_all {
+        true => content.replace(&edit.old_string, &edit.new_string),
+        false => content.replacen(&edit.old_string, &edit.new_string, 1),
+    })
+}
+
+fn read_file(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok()
+}
+
+impl std::fmt::Debug for FileChangeTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = self.lock();
+        f.debug_struct("FileChangeTracker")
+            .field("base_dir", &inner.base_dir)
+            .field("files", &inner.session.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_parser::parse_message;
+    use serde_json::{json, Value};
+
+    fn tool_use(id: &str, name: &str, input: Value) -> Message {
+        parse_message(json!({
+            "type": "assistant",
+            "message": {"content": [{"type": "tool_use", "id": id, "name": name, "input": input}]}
+        }))
+        .unwrap()
+    }
+
+    fn tool_result(id: &str, is_error: bool) -> Message {
+        parse_message(json!({
+            "type": "user",
+            "message": {"content": [
+                {"type": "tool_result", "tool_use_id": id, "content": "ok", "is_error": is_error}
+            ]}
+        }))
+        .unwrap()
+    }
+
+    fn result() -> Message {
+        parse_message(json!({"type": "result", "subtype": "success"})).unwrap()
+    }
+
+    #[test]
+    fn replays_edits_from_the_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+        let tracker = FileChangeTracker::new().with_base_dir(dir.path());
+        let file = dir.path().join("lib.rs").display().to_string();
+
+        tracker.observe(&tool_use(
+            "e1",
+            "Edit",
+            json!({"file_path": file, "old_string": "fn b() {}", "new_string": "fn c() {}"}),
+        ));
+        tracker.observe(&tool_result("e1", false));
+        // A failed edit changes nothing.
+        tracker.observe(&tool_use(
+            "e2",
+            "Edit",
+            json!({"file_path": file, "old_string": "fn a() {}", "new_string": ""}),
+        ));
+        tracker.observe(&tool_result("e2", true));
+
+        let turn = tracker.observe(&result());
+        assert_eq!(turn.len(), 1);
+        assert_eq!(turn[0].path, Path::new("lib.rs"));
+        assert_eq!(turn[0].after.as_deref(), Some("fn a() {}\nfn c() {}\n"));
+        assert_eq!(
+            tracker.patch(),
+            "--- a/lib.rs\n+++ b/lib.rs\n@@ -1,2 +1,2 @@\n fn a() {}\n-fn b() {}\n+fn c() {}\n"
+        );
+
+        // The next turn starts empty but the session keeps accumulating.
+        tracker.observe(&tool_use(
+            "w1",
+            "Write",
+            json!({"file_path": "notes.md", "content": "hi\n"}),
+        ));
+        tracker.observe(&tool_result("w1", false));
+        let turn = tracker.observe(&result());
+        assert_eq!(turn.len(), 1);
+        assert!(turn[0].is_created());
+        assert!(turn[0]
+            .unified_diff()
+            .starts_with("--- /dev/null\n+++ b/notes.md\n"));
+        assert_eq!(tracker.changes().len(), 2);
+    }
+
+    #[test]
+    fn relative_paths_resolve_against_the_session_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "old\n").unwrap();
+        let tracker = FileChangeTracker::new();
+        tracker.observe(
+            &parse_message(json!({"type": "system", "subtype": "init", "cwd": dir.path()}))
+                .unwrap(),
+        );
+
+        tracker.observe(&tool_use(
+            "e1",
+            "Edit",
+            json!({"file_path": "a.txt", "old_string": "old", "new_string": "new"}),
+        ));
+        tracker.observe(&tool_result("e1", false));
+
+        let changes = tracker.changes();
+        assert_eq!(changes[0].before.as_deref(), Some("old\n"));
+        assert_eq!(changes[0].after.as_deref(), Some("new\n"));
+    }
+
+    #[test]
+    fn edits_applied_before_they_are_observed_need_the_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        let edit = json!({"file_path": file, "old_string": "old", "new_string": "new"});
+        // The CLI has already written the edit when the tool use is observed.
+        std::fs::write(&file, "new\n").unwrap();
+
+        let stream_only = FileChangeTracker::new();
+        stream_only.observe(&tool_use("e1", "Edit", edit.clone()));
+        stream_only.observe(&tool_result("e1", false));
+        assert!(stream_only.changes().is_empty());
+
+        let hooked = FileChangeTracker::new();
+        hooked.observe(&tool_use("e1", "Edit", edit.clone()));
+        let input: PostToolUseInput = serde_json::from_value(json!({
+            "tool_use_id": "e1",
+            "tool_name": "Edit",
+            "tool_input": edit,
+            "tool_response": {"originalFile": "old\n"}
+        }))
+        .unwrap();
+        hooked.observe_post_tool_use(&input);
+        hooked.observe(&tool_result("e1", false));
+
+        let changes = hooked.changes();
+        assert_eq!(changes[0].before.as_deref(), Some("old\n"));
+        assert_eq!(changes[0].after.as_deref(), Some("new\n"));
+    }
+
+    #[test]
+    fn post_tool_use_original_content_wins_and_is_not_applied_twice() {
+        let tracker = FileChangeTracker::new();
+        let input: PostToolUseInput = serde_json::from_value(json!({
+            "tool_use_id": "m1",
+            "tool_name": "MultiEdit",
+            "tool_input": {"file_path": "/nowhere/a.txt", "edits": [
+                {"old_string": "one", "new_string": "two"},
+                {"old_string": "two", "new_string": "three", "replace_all": true}
+            ]},
+            "tool_response": {"originalFileContents": "one two\n"}
+        }))
+        .unwrap();
+        tracker.observe_post_tool_use(&input);
+
+        // The same tool use arriving on the stream afterwards is ignored.
+        tracker.observe(&tool_use("m1", "MultiEdit", input.tool_input.clone()));
+        tracker.observe(&tool_result("m1", false));
+
+        // A file created by Write reports a `null` original.
+        let dir = tempfile::tempdir().unwrap();
+        let new_file = dir.path().join("new.txt");
+        std::fs::write(&new_file, "made\n").unwrap();
+        let input: PostToolUseInput = serde_json::from_value(json!({
+            "tool_name": "Write",
+            "tool_input": {"file_path": new_file, "content": "made\n"},
+            "tool_response": {"type": "create", "originalFile": null}
+        }))
+        .unwrap();
+        tracker.observe_post_tool_use(&input);
+
+        let changes = tracker.changes();
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().any(|c| c.is_created()));
+        let changes: Vec<_> = changes.into_iter().filter(|c| !c.is_created()).collect();
+        assert_eq!(changes[0].before.as_deref(), Some("one two\n"));
+        assert_eq!(changes[0].after.as_deref(), Some("three three\n"));
+    }
+}
@@ -0,0 +1,7 @@
+//! Following what the agent did during a session.
+
+pub mod file_changes;
+pub mod tool_calls;
+
+pub use file_changes::{combined_patch, FileChange, FileChangeTracker};
+pub use tool_calls::{ToolCall, ToolCallEvent, ToolCallStatus, ToolCallStream, ToolCallTracker};
@@ -0,0 +1,337 @@
+//! Pairing tool uses with their results.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use serde_json::Value;
+use tokio_stream::Stream;
+
+use crate::error::Result;
+use crate::types::content::{ContentBlock, ToolResultContent};
+use crate::types::messages::{Message, UserContent};
+
+/// Prefix of the user message the CLI emits when a turn is interrupted.
+const INTERRUPT_MARKER: &str = "[Request interrupted by user";
+
+/// How a tool call ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ToolCallStatus {
+    /// A result arrived.
+    Completed,
+    /// The turn ended without a result.
+    TurnEnded,
+    /// The turn was interrupted before a result arrived.
+    Interrupted,
+    /// The message stream ended without a result.
+    StreamEnded,
+}
+
+/// A tool use and, if it arrived, its result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub input: Value,
+    /// `None` unless `status` is [`ToolCallStatus::Completed`].
+    pub result: Option<ToolResultContent>,
+    pub is_error: bool,
+    pub started_at: SystemTime,
+    /// When the result arrived.
+    pub finished_at: Option<SystemTime>,
+    /// Tool use (e.g. a `Task` subagent) this call was made under.
+    pub parent_tool_use_id: Option<String>,
+    pub status: ToolCallStatus,
+}
+
+impl ToolCall {
+    /// Whether the call finished without a result.
+    pub fn is_unfinished(&self) -> bool {
+        self.status != ToolCallStatus::Completed
+    }
+}
+
+/// Matches `ToolUse` blocks in assistant messages with the `ToolResult`
+/// blocks that answer them in later user messages.
+///
+/// Feed it messages with [`observe`](Self::observe), or wrap a message
+/// stream with [`track`](Self::track). Calls still open when a turn ends
+/// (a `Result` message) or is interrupted are reported as unfinished.
+#[derive(Debug, Default)]
+pub struct ToolCallTracker {
+    /// Open calls, oldest first.
+    pending: Vec<ToolCall>,
+}
+
+impl ToolCallTracker {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tool calls still waiting for a result, oldest first.
+    pub fn pending(&self) -> &[ToolCall] {
+        &self.pending
+    }
+
+    /// Record `message` and return the calls it completes or abandons.
+    pub fn observe(&mut self, message: &Message) -> Vec<ToolCall> {
+        match message {
+            Message::Assistant { message } => {
+                let now = SystemTime::now();
+                for block in &message.content {
+                    if let ContentBlock::ToolUse { id, name, input } = block {
+                        self.pending.push(ToolCall {
+                            id: id.clone(),
+                            name: name.clone(),
+                            input: input.clone(),
+                            result: None,
+                            is_error: false,
+                            started_at: now,
+                            finished_at: None,
+                            parent_tool_use_id: message.parent_tool_use_id.clone(),
+                            status: ToolCallStatus::TurnEnded,
+                        });
+                    }
+                }
+                Vec::new()
+            }
+            Message::User { message } => {
+                let blocks = match &message.content {
+                    UserContent::Blocks(blocks) => blocks.as_slice(),
+                    _ => &[],
+                };
+                let now = SystemTime::now();
+                let mut done = Vec::new();
+                for block in blocks {
+                    let ContentBlock::ToolResult {
+                        tool_use_id,
+                        content,
+                        is_error,
+                    } = block
+                    else {
+                        continue;
+                    };
+                    let Some(index) = self.pending.iter().position(|c| &c.id == tool_use_id) else {
+                        tracing::debug!(%tool_use_id, "tool result without a matching tool use");
+                        continue;
+                    };
+                    let mut call = self.pending.remove(index);
+                    call.result = Some(content.clone());
+                    call.is_error = *is_error;
+                    call.finished_at = Some(now);
+                    call.status = ToolCallStatus::Completed;
+                    done.push(call);
+                }
+                if is_interrupt(&message.content) {
+                    done.extend(self.finish(ToolCallStatus::Interrupted));
+                }
+                done
+            }
+            Message::Result { .. } => self.finish(ToolCallStatus::TurnEnded),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Close every open call with `status` and return them.
+    pub fn finish(&mut self, status: ToolCallStatus) -> Vec<ToolCall> {
+        let mut calls = std::mem::take(&mut self.pending);
+        for call in &mut calls {
+            call.status = status;
+        }
+        calls
+    }
+
+    /// Wrap a message stream, forwarding each message followed by the tool
+    /// calls it completes or abandons.
+    pub fn track<S>(self, stream: S) -> ToolCallStream<S>
+    where
+        S: Stream<Item = Result<Message>> + Unpin,
+    {
+        ToolCallStream {
+            inner: stream,
+            tracker: self,
+            queued: VecDeque::new(),
+            done: false,
+        }
+    }
+}
+
+fn is_interrupt(content: &UserContent) -> bool {
+    match content {
+        UserContent::Text(text) => text.starts_with(INTERRUPT_MARKER),
+        UserContent::Blocks(blocks) => blocks
+            .iter()
+            .any(|b| b.as_text().is_some_and(|t| t.starts_with(INTERRUPT_MARKER))),
+        UserContent::Empty => false,
+    }
+}
+
+/// An item of a [`ToolCallStream`].
+#[derive(Debug, Clone)]
+pub enum ToolCallEvent {
+    /// A message from the underlying stream.
+    Message(Message),
+    /// A tool call completed by, or left unfinished at, the preceding message.
+    ToolCall(ToolCall),
+}
+
+/// Stream returned by [`ToolCallTracker::track`].
+///
+/// When the underlying stream ends, calls still open are emitted with
+/// [`ToolCallStatus::StreamEnded`].
+pub struct ToolCallStream<S> {
+    inner: S,
+    tracker: ToolCallTracker,
+    queued: VecDeque<ToolCallEvent>,
+    done: bool,
+}
+
+impl<S> ToolCallStream<S> {
+    /// Tool calls still waiting for a result.
+    pub fn pending(&self) -> &[ToolCall] {
+        self.tracker.pending()
+    }
+}
+
+impl<S> Stream for ToolCallStream<S>
+where
+    S: Stream<Item = Result<Message>> + Unpin,
+{
+    type Item = Result<ToolCallEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Some(event) = this.queued.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(message))) => {
+                    let calls = this.tracker.observe(&message);
+                    this.queued.push_back(ToolCallEvent::Message(message));
+                    this.queued
+                        .extend(calls.into_iter().map(ToolCallEvent::ToolCall));
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => {
+                    this.done = true;
+                    let calls = this.tracker.finish(ToolCallStatus::StreamEnded);
+                    this.queued
+                        .extend(calls.into_iter().map(ToolCallEvent::ToolCall));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_parser::parse_message;
+    use serde_json::json;
+    use tokio_stream::StreamExt;
+
+    fn assistant(parent: Option<&str>, blocks: Value) -> Message {
+        parse_message(json!({
+            "type": "assistant",
+            "parent_tool_use_id": parent,
+            "message": {"content": blocks}
+        }))
+        .unwrap()
+    }
+
+    fn user(content: Value) -> Message {
+        parse_message(json!({"type": "user", "message": {"content": content}})).unwrap()
+    }
+
+    fn tool_use(id: &str, name: &str) -> Value {
+        json!({"type": "tool_use", "id": id, "name": name, "input": {"command": "ls"}})
+    }
+
+    fn tool_result(id: &str, is_error: bool) -> Value {
+        json!({"type": "tool_result", "tool_use_id": id, "content": "out", "is_error": is_error})
+    }
+
+    fn result() -> Message {
+        parse_message(json!({"type": "result", "subtype": "success"})).unwrap()
+    }
+
+    #[test]
+    fn pairs_results_with_tool_uses() {
+        let mut tracker = ToolCallTracker::new();
+        let started = assistant(None, json!([tool_use("a", "Bash"), tool_use("b", "Task")]));
+        assert!(tracker.observe(&started).is_empty());
+        assert!(tracker
+            .observe(&assistant(Some("b"), json!([tool_use("c", "Read")])))
+            .is_empty());
+        assert_eq!(tracker.pending().len(), 3);
+
+        let done = tracker.observe(&user(json!([
+            tool_result("c", false),
+            tool_result("a", true)
+        ])));
+        assert_eq!(done.len(), 2);
+        assert_eq!(done[0].name, "Read");
+        assert_eq!(done[0].parent_tool_use_id.as_deref(), Some("b"));
+        assert_eq!(done[0].result, Some(ToolResultContent::Text("out".into())));
+        assert!(done[1].is_error);
+        assert!(done[1].finished_at.unwrap() >= done[1].started_at);
+        assert_eq!(done[1].status, ToolCallStatus::Completed);
+
+        // `b` never got a result.
+        let abandoned = tracker.observe(&result());
+        assert_eq!(abandoned.len(), 1);
+        assert_eq!(abandoned[0].id, "b");
+        assert_eq!(abandoned[0].status, ToolCallStatus::TurnEnded);
+        assert!(abandoned[0].is_unfinished());
+        assert!(tracker.pending().is_empty());
+    }
+
+    #[test]
+    fn interrupt_abandons_open_calls() {
+        let mut tracker = ToolCallTracker::new();
+        tracker.observe(&assistant(None, json!([tool_use("a", "Bash")])));
+        let abandoned = tracker.observe(&user(json!([
+            {"type": "text", "text": "[Request interrupted by user for tool use]"}
+        ])));
+        assert_eq!(abandoned.len(), 1);
+        assert_eq!(abandoned[0].status, ToolCallStatus::Interrupted);
+        assert_eq!(abandoned[0].result, None);
+    }
+
+    #[tokio::test]
+    async fn stream_forwards_messages_then_calls() {
+        let messages = vec![
+            Ok(assistant(
+                None,
+                json!([tool_use("a", "Bash"), tool_use("b", "Bash")]),
+            )),
+            Ok(user(json!([tool_result("a", false)]))),
+        ];
+        let events: Vec<_> = ToolCallTracker::new()
+            .track(tokio_stream::iter(messages))
+            .map(|e| e.unwrap())
+            .collect()
+            .await;
+
+        let summary: Vec<_> = events
+            .iter()
+            .map(|e| match e {
+                ToolCallEvent::Message(Message::Assistant { .. }) => "assistant".to_string(),
+                ToolCallEvent::Message(_) => "user".to_string(),
+                ToolCallEvent::ToolCall(call) => format!("{}:{:?}", call.id, call.status),
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            ["assistant", "user", "a:Completed", "b:StreamEnded"]
+        );
+    }
+}
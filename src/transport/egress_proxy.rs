@@ -0,0 +1,328 @@
+use std::net::SocketAddr;
+
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::Result;
+use crate::types::sandbox::{EgressEvent, EgressPolicy};
+
+/// Maximum size of a proxy request head.
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// A local HTTP proxy that enforces an [`EgressPolicy`].
+///
+/// Handles `CONNECT host:port` tunnels (HTTPS) only; plain HTTP requests are
+/// refused, since a kept-alive connection could carry requests to other
+/// hosts. Every decision is reported as a `system`/`egress` message on the
+/// transport's incoming channel; a connection waits until its event fits.
+pub(crate) struct EgressProxy {
+    addr: SocketAddr,
+}
+
+impl EgressProxy {
+    /// Bind to an ephemeral loopback port and serve until `cancel` fires.
+    pub(crate) async fn start(
+        policy: EgressPolicy,
+        events: mpsc::Sender<Result<Value>>,
+        cancel: CancellationToken,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => {
+                            let policy = policy.clone();
+                            let events = events.clone();
+                            let cancel = cancel.clone();
+                            tokio::spawn(async move {
+                                tokio::select! {
+                                    _ = cancel.cancelled() => {}
+                                    result = handle_connection(stream, &policy, &events) => {
+                                        if let Err(e) = result {
+                                            tracing::debug!("egress proxy connection failed: {e}");
+                                        }
+                                    }
+                                }
+                            });
+                        }
+                        Err(e) => {
+                            tracing::warn!("egress proxy accept failed: {e}");
+                        }
+                    },
+                }
+            }
+        });
+
+        Ok(Self { addr })
+    }
+
+    /// Proxy URL for `HTTPS_PROXY`/`HTTP_PROXY`.
+    pub(crate) fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+async fn handle_connection(
+    mut client: TcpStream,
+    policy: &EgressPolicy,
+    events: &mpsc::Sender<Result<Value>>,
+) -> std::io::Result<()> {
+    let (head, rest) = match read_head(&mut client).await? {
+        Some(parts) => parts,
+        None => {
+            client.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n").await?;
+            return Ok(());
+        }
+    };
+
+    let request_line = head.lines().next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let is_connect = method.eq_ignore_ascii_case("CONNECT");
+
+    let Some((host, port)) = parse_target(target, is_connect) else {
+        client.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n").await?;
+        return Ok(());
+    };
+
+    if !is_connect {
+        report(events, &host, port, false).await;
+        client
+            .write_all(b"HTTP/1.1 405 Method Not Allowed\r\nAllow: CONNECT\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .await?;
+        return Ok(());
+    }
+
+    let allowed = policy.is_allowed(&host);
+    report(events, &host, port, allowed).await;
+
+    if !allowed {
+        client
+            .write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .await?;
+        return Ok(());
+    }
+
+    let mut upstream = match TcpStream::connect((host.as_str(), port)).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            client
+                .write_all(b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .await?;
+            return Err(e);
+        }
+    };
+
+    client
+        .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+        .await?;
+    upstream.write_all(&rest).await?;
+
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+/// Read up to and including the blank line ending the request head.
+///
+/// Returns the head and any bytes read past it, or `None` if the head is
+/// malformed or too large.
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<Option<(String, Vec<u8>)>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buf.split_off(pos + 4);
+            return Ok(String::from_utf8(buf).ok().map(|head| (head, rest)));
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            return Ok(None);
+        }
+    }
+}
+
+/// Extract host and port from a CONNECT authority or an absolute `http://` URI.
+fn parse_target(target: &str, is_connect: bool) -> Option<(String, u16)> {
+    let (authority, default_port) = if is_connect {
+        (target, None)
+    } else {
+        let rest = target.strip_prefix("http://")?;
+        (rest.split('/').next()?, Some(80))
+    };
+
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, port.parse().ok()?),
+        _ => (authority, default_port?),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return None;
+    }
+    Some((host.to_string(), port))
+}
+
+async fn report(events: &mpsc::Sender<Result<Value>>, host: &str, port: u16, allowed: bool) {
+    if allowed {
+        tracing::debug!(host, port, "egress allowed");
+    } else {
+        tracing::info!(host, port, "egress blocked");
+    }
+
+    let event = EgressEvent {
+        host: host.to_string(),
+        port,
+        allowed,
+    };
+    let mut value = serde_json::to_value(event).unwrap_or(Value::Null);
+    value["type"] = "system".into();
+    value["subtype"] = "egress".into();
+    // Fails only once the transport is gone, and the connection with it.
+    let _ = events.send(Ok(value)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_targets() {
+        assert_eq!(parse_target("example.com:443", true), Some(("example.com".into(), 443)));
+        assert_eq!(parse_target("[::1]:8443", true), Some(("::1".into(), 8443)));
+        assert_eq!(parse_target("example.com", true), None);
+        assert_eq!(
+            parse_target("http://example.com/path?q=1", false),
+            Some(("example.com".into(), 80))
+        );
+        assert_eq!(
+            parse_target("http://example.com:8080/", false),
+            Some(("example.com".into(), 8080))
+        );
+        assert_eq!(parse_target("/relative", false), None);
+    }
+
+    /// Stand-in upstream that echoes whatever it receives.
+    async fn echo_upstream() -> SocketAddr {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut r, mut w) = stream.split();
+                    let _ = tokio::io::copy(&mut r, &mut w).await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn read_response_head(stream: &mut TcpStream) -> String {
+        let (head, _) = read_head(stream).await.unwrap().unwrap();
+        head
+    }
+
+    #[tokio::test]
+    async fn tunnels_allowed_and_blocks_denied_hosts() {
+        let upstream = echo_upstream().await;
+        let (events_tx, mut events_rx) = mpsc::channel(8);
+        let cancel = CancellationToken::new();
+        let policy = EgressPolicy {
+            allow: vec!["127.0.0.1".into()],
+            deny: vec![],
+        };
+        let proxy = EgressProxy::start(policy, events_tx, cancel.clone()).await.unwrap();
+
+        // Allowed: CONNECT tunnels bytes to the upstream and back.
+        let mut client = TcpStream::connect(proxy.addr).await.unwrap();
+        client
+            .write_all(format!("CONNECT {upstream} HTTP/1.1\r\nHost: {upstream}\r\n\r\n").as_bytes())
+            .await
+            .unwrap();
+        assert!(read_response_head(&mut client).await.starts_with("HTTP/1.1 200"));
+        client.write_all(b"ping").await.unwrap();
+        let mut echoed = [0u8; 4];
+        client.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"ping");
+
+        let event = events_rx.recv().await.unwrap().unwrap();
+        assert_eq!(event["type"], "system");
+        assert_eq!(event["subtype"], "egress");
+        assert_eq!(event["host"], "127.0.0.1");
+        assert_eq!(event["port"], upstream.port());
+        assert_eq!(event["allowed"], true);
+
+        // Denied: the proxy answers 403 without contacting the host.
+        let mut client = TcpStream::connect(proxy.addr).await.unwrap();
+        client
+            .write_all(b"CONNECT blocked.example:443 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(read_response_head(&mut client).await.starts_with("HTTP/1.1 403"));
+
+        let event = events_rx.recv().await.unwrap().unwrap();
+        let message = crate::message_parser::parse_message(event).unwrap();
+        assert_eq!(
+            message.egress_event(),
+            Some(EgressEvent {
+                host: "blocked.example".into(),
+                port: 443,
+                allowed: false,
+            })
+        );
+
+        cancel.cancel();
+    }
+
+    #[tokio::test]
+    async fn events_wait_for_a_slow_consumer() {
+        let (events_tx, mut events_rx) = mpsc::channel(1);
+        events_tx.send(Ok(Value::Null)).await.unwrap();
+        let policy = EgressPolicy {
+            allow: vec![],
+            deny: vec!["*".into()],
+        };
+        let proxy = EgressProxy::start(policy, events_tx, CancellationToken::new())
+            .await
+            .unwrap();
+
+        let mut client = TcpStream::connect(proxy.addr).await.unwrap();
+        client
+            .write_all(b"CONNECT blocked.example:443 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(events_rx.recv().await.unwrap().unwrap().is_null());
+        let event = events_rx.recv().await.unwrap().unwrap();
+        assert_eq!(event["host"], "blocked.example");
+        assert_eq!(event["allowed"], false);
+        assert!(read_response_head(&mut client).await.starts_with("HTTP/1.1 403"));
+    }
+
+    #[tokio::test]
+    async fn refuses_plain_http_requests() {
+        let upstream = echo_upstream().await;
+        let (events_tx, mut events_rx) = mpsc::channel(8);
+        let proxy = EgressProxy::start(EgressPolicy::default(), events_tx, CancellationToken::new())
+            .await
+            .unwrap();
+
+        let request = format!("GET http://{upstream}/hello HTTP/1.1\r\nHost: {upstream}\r\n\r\n");
+        let mut client = TcpStream::connect(proxy.addr).await.unwrap();
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        assert!(read_response_head(&mut client).await.starts_with("HTTP/1.1 405"));
+        let event = events_rx.recv().await.unwrap().unwrap();
+        assert_eq!(event["port"], upstream.port());
+        assert_eq!(event["allowed"], false);
+    }
+}
@@ -0,0 +1,96 @@
+//! In-memory transport for driving [`Query`](crate::query::Query) in tests.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use serde_json::Value;
+use tokio::sync::{mpsc, Notify};
+
+use crate::error::{Error, Result};
+
+use super::{Transport, TransportChannels, TransportWriter};
+
+/// Transport whose "CLI" is a [`FakeCli`] held by the test.
+pub(crate) struct FakeTransport {
+    channels: Option<TransportChannels>,
+    stdin_closed: Arc<Notify>,
+}
+
+/// The CLI side of a [`FakeTransport`].
+pub(crate) struct FakeCli {
+    /// Messages the CLI prints to stdout.
+    pub stdout: mpsc::Sender<Result<Value>>,
+    /// Messages the SDK writes to the CLI's stdin.
+    pub stdin: mpsc::Receiver<Value>,
+    stdin_closed: Arc<Notify>,
+}
+
+/// A connected transport and the CLI end of it.
+pub(crate) fn fake_transport() -> (FakeTransport, FakeCli) {
+    let (stdout_tx, stdout_rx) = mpsc::channel(64);
+    let (stdin_tx, stdin_rx) = mpsc::channel(64);
+    let stdin_closed = Arc::new(Notify::new());
+    let transport = FakeTransport {
+        channels: Some((stdout_rx, TransportWriter::new(stdin_tx))),
+        stdin_closed: stdin_closed.clone(),
+    };
+    let cli = FakeCli {
+        stdout: stdout_tx,
+        stdin: stdin_rx,
+        stdin_closed,
+    };
+    (transport, cli)
+}
+
+impl FakeCli {
+    /// Print `message` on stdout.
+    pub async fn send(&self, message: Value) {
+        self.stdout.send(Ok(message)).await.unwrap();
+    }
+
+    /// The next message written to stdin.
+    pub async fn recv(&mut self) -> Value {
+        self.stdin.recv().await.expect("stdin closed")
+    }
+
+    /// Answer the next control request on stdin with a success response.
+    pub async fn answer_control_request(&mut self) -> Value {
+        let request = self.recv().await;
+        assert_eq!(request["type"], "control_request");
+        self.send(serde_json::json!({
+            "type": "control_response",
+            "response": {
+                "subtype": "success",
+                "request_id": request["request_id"],
+                "response": {}
+            }
+        }))
+        .await;
+        request
+    }
+
+    /// Wait until the SDK closes stdin.
+    pub async fn stdin_closed(&self) {
+        self.stdin_closed.notified().await;
+    }
+}
+
+impl Transport for FakeTransport {
+    fn connect(&mut self) -> Pin<Box<dyn Future<Output = Result<TransportChannels>> + Send + '_>> {
+        Box::pin(async move { self.channels.take().ok_or(Error::AlreadyConnected) })
+    }
+
+    fn end_input(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        self.stdin_closed.notify_one();
+        Box::pin(async { Ok(()) })
+    }
+
+    fn close(&mut self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn is_ready(&self) -> bool {
+        self.channels.is_none()
+    }
+}
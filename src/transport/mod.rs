@@ -1,4 +1,9 @@
 pub mod cli_discovery;
+mod egress_proxy;
+#[cfg(test)]
+pub(crate) mod fake;
+#[cfg(target_os = "linux")]
+mod namespaces;
 pub mod subprocess;
 
 use std::future::Future;
@@ -32,13 +37,16 @@ impl TransportWriter {
     }
 }
 
+/// Incoming message receiver and outgoing writer returned by [`Transport::connect`].
+pub type TransportChannels = (mpsc::Receiver<Result<Value>>, TransportWriter);
+
 /// Trait for a transport layer that communicates with the Claude CLI.
 #[allow(dead_code)]
 pub trait Transport: Send + Sync {
     /// Connect to the CLI process.
     ///
     /// Returns a receiver for incoming messages and a writer for outgoing messages.
-    fn connect(&mut self) -> Pin<Box<dyn Future<Output = Result<(mpsc::Receiver<Result<Value>>, TransportWriter)>> + Send + '_>>;
+    fn connect(&mut self) -> Pin<Box<dyn Future<Output = Result<TransportChannels>> + Send + '_>>;
 
     /// Signal end of input (close stdin).
     fn end_input(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>>;
@@ -48,4 +56,9 @@ pub trait Transport: Send + Sync {
 
     /// Check if the transport is still connected.
     fn is_ready(&self) -> bool;
+
+    /// Exit code of the CLI process, once it has exited.
+    fn exit_code(&self) -> Option<i32> {
+        None
+    }
 }
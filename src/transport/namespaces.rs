@@ -0,0 +1,336 @@
+//! Linux sandbox backend for [`SandboxType::Namespaces`](crate::types::sandbox::SandboxType::Namespaces).
+//!
+//! Filesystem confinement uses Landlock; network isolation runs the CLI in
+//! fresh user and network namespaces. The Landlock ruleset is built in the
+//! parent and only enforced (together with `unshare`) in a `pre_exec` hook,
+//! so the forked child does nothing but async-signal-safe syscalls.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use tokio::process::Command;
+
+use crate::error::{Error, Result};
+use crate::types::sandbox::SandboxSettings;
+
+/// System paths the CLI and the tools it runs need to read.
+const SYSTEM_READ_PATHS: &[&str] = &[
+    "/bin", "/sbin", "/usr", "/lib", "/lib32", "/lib64", "/etc", "/opt", "/nix", "/proc", "/sys",
+    "/dev", "/run",
+];
+
+/// Device files that stay writable inside the sandbox.
+const SYSTEM_WRITE_PATHS: &[&str] = &["/dev/null", "/dev/zero", "/dev/full", "/dev/tty"];
+
+// Landlock filesystem access rights, from <linux/landlock.h>.
+const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+const ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+const ACCESS_FS_MAKE_DIR: u64 = 1 << 7;
+const ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+const ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+const ACCESS_FS_MAKE_FIFO: u64 = 1 << 10;
+const ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+const ACCESS_FS_MAKE_SYM: u64 = 1 << 12;
+/// Landlock ABI 2.
+const ACCESS_FS_REFER: u64 = 1 << 13;
+/// Landlock ABI 3.
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+const ACCESS_READ: u64 = ACCESS_FS_EXECUTE | ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+const ACCESS_WRITE: u64 = ACCESS_FS_WRITE_FILE
+    | ACCESS_FS_REMOVE_DIR
+    | ACCESS_FS_REMOVE_FILE
+    | ACCESS_FS_MAKE_CHAR
+    | ACCESS_FS_MAKE_DIR
+    | ACCESS_FS_MAKE_REG
+    | ACCESS_FS_MAKE_SOCK
+    | ACCESS_FS_MAKE_FIFO
+    | ACCESS_FS_MAKE_BLOCK
+    | ACCESS_FS_MAKE_SYM
+    | ACCESS_FS_REFER
+    | ACCESS_FS_TRUNCATE;
+/// Rights that may be granted on a non-directory.
+const ACCESS_FILE: u64 =
+    ACCESS_FS_EXECUTE | ACCESS_FS_WRITE_FILE | ACCESS_FS_READ_FILE | ACCESS_FS_TRUNCATE;
+
+const CREATE_RULESET_VERSION: libc::c_uint = 1;
+const RULE_PATH_BENEATH: libc::c_int = 1;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+/// Configure `cmd` to start the CLI inside the sandbox.
+///
+/// The CLI may read system paths, its own install directory, the node
+/// runtime and `read_only_paths`, and may write only beneath `cwd` and
+/// `writable_paths`, plus the temp dirs and its home state if enabled in
+/// `settings`. `env` is the CLI's extra environment, which may set `HOME`.
+/// Without `allow_network` it gets an empty network namespace.
+pub(crate) fn apply(
+    cmd: &mut Command,
+    settings: &SandboxSettings,
+    cwd: &Path,
+    cli_path: &Path,
+    env: &HashMap<String, String>,
+) -> Result<()> {
+    if !settings.allow_network && settings.egress.is_some() {
+        return Err(Error::Sandbox(
+            "egress requires allow_network: the proxy is unreachable from an empty network namespace".into(),
+        ));
+    }
+    let abi = landlock_abi()
+        .ok_or_else(|| Error::Sandbox("Landlock is not supported by this kernel".into()))?;
+    let handled = handled_access(abi);
+    let ruleset = create_ruleset(handled)?;
+
+    for path in SYSTEM_READ_PATHS {
+        add_rule(&ruleset, Path::new(path), ACCESS_READ & handled, false)?;
+    }
+    for dir in cli_dirs(cli_path) {
+        add_rule(&ruleset, &dir, ACCESS_READ & handled, false)?;
+    }
+    if let Ok(node) = which::which("node") {
+        for dir in cli_dirs(&node) {
+            add_rule(&ruleset, &dir, ACCESS_READ & handled, false)?;
+        }
+    }
+    for path in &settings.read_only_paths {
+        add_rule(&ruleset, path, ACCESS_READ & handled, true)?;
+    }
+
+    for path in SYSTEM_WRITE_PATHS {
+        add_rule(&ruleset, Path::new(path), (ACCESS_READ | ACCESS_WRITE) & handled, false)?;
+    }
+    add_rule(&ruleset, cwd, (ACCESS_READ | ACCESS_WRITE) & handled, true)?;
+    if settings.writable_tmp {
+        for dir in temp_dirs() {
+            add_rule(&ruleset, &dir, (ACCESS_READ | ACCESS_WRITE) & handled, false)?;
+        }
+    }
+    if settings.home_access {
+        let var = |name: &str| {
+            env.get(name)
+                .map(OsString::from)
+                .or_else(|| std::env::var_os(name))
+        };
+        for path in home_state(var("HOME"), var("CLAUDE_CONFIG_DIR")) {
+            add_rule(&ruleset, &path, (ACCESS_READ | ACCESS_WRITE) & handled, false)?;
+        }
+    }
+    for path in &settings.writable_paths {
+        add_rule(&ruleset, path, (ACCESS_READ | ACCESS_WRITE) & handled, true)?;
+    }
+
+    let id_maps = (!settings.allow_network).then(IdMaps::current);
+
+    // SAFETY: the hook runs in the forked child before exec and only issues
+    // async-signal-safe syscalls; all allocation happened above.
+    unsafe {
+        cmd.pre_exec(move || {
+            if let Some(maps) = &id_maps {
+                maps.enter_namespaces()?;
+            }
+            restrict_self(&ruleset)
+        });
+    }
+    Ok(())
+}
+
+/// The Landlock ABI version supported by the running kernel, if any.
+pub(crate) fn landlock_abi() -> Option<u32> {
+    // SAFETY: a null attr with size 0 and the VERSION flag only queries the ABI.
+    let abi = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0usize,
+            CREATE_RULESET_VERSION,
+        )
+    };
+    u32::try_from(abi).ok().filter(|&abi| abi > 0)
+}
+
+fn handled_access(abi: u32) -> u64 {
+    let mut access = ACCESS_READ | (ACCESS_WRITE & !(ACCESS_FS_REFER | ACCESS_FS_TRUNCATE));
+    if abi >= 2 {
+        access |= ACCESS_FS_REFER;
+    }
+    if abi >= 3 {
+        access |= ACCESS_FS_TRUNCATE;
+    }
+    access
+}
+
+fn create_ruleset(handled_access_fs: u64) -> Result<OwnedFd> {
+    let attr = RulesetAttr { handled_access_fs };
+    // SAFETY: `attr` is a valid ruleset attr and its size is passed alongside.
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr as *const RulesetAttr,
+            std::mem::size_of::<RulesetAttr>(),
+            0u32,
+        )
+    };
+    if fd < 0 {
+        return Err(Error::Sandbox(format!(
+            "landlock_create_ruleset failed: {}",
+            io::Error::last_os_error()
+        )));
+    }
+    // SAFETY: the syscall returned a fresh file descriptor we now own.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) })
+}
+
+/// Allow `access` beneath `path`. Unopenable paths are skipped unless `required`.
+fn add_rule(ruleset: &OwnedFd, path: &Path, access: u64, required: bool) -> Result<()> {
+    let file = match std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+        .open(path)
+    {
+        Ok(file) => file,
+        Err(_) if !required => return Ok(()),
+        Err(e) => {
+            return Err(Error::Sandbox(format!("cannot open {}: {e}", path.display())));
+        }
+    };
+    let is_dir = file.metadata().map(|m| m.is_dir()).unwrap_or(false);
+    let attr = PathBeneathAttr {
+        allowed_access: if is_dir { access } else { access & ACCESS_FILE },
+        parent_fd: file.as_raw_fd(),
+    };
+    // SAFETY: both fds are open and `attr` is a valid path-beneath attr.
+    let rc = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_add_rule,
+            ruleset.as_raw_fd(),
+            RULE_PATH_BENEATH,
+            &attr as *const PathBeneathAttr,
+            0u32,
+        )
+    };
+    if rc != 0 {
+        return Err(Error::Sandbox(format!(
+            "landlock_add_rule({}) failed: {}",
+            path.display(),
+            io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+/// Directories holding the CLI: where it was found and where it links to.
+fn cli_dirs(cli_path: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = cli_path.parent().map(Path::to_path_buf).into_iter().collect();
+    if let Some(dir) = std::fs::canonicalize(cli_path)
+        .ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+    {
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Scratch directories tools expect to write to.
+fn temp_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from("/tmp"), PathBuf::from("/var/tmp")];
+    let tmpdir = std::env::temp_dir();
+    if !dirs.contains(&tmpdir) {
+        dirs.push(tmpdir);
+    }
+    dirs
+}
+
+/// Where the CLI keeps its own state: the config dir and `~/.claude.json`.
+fn home_state(home: Option<OsString>, config_dir: Option<OsString>) -> Vec<PathBuf> {
+    let home = home.map(PathBuf::from);
+    let mut paths = Vec::new();
+    match config_dir {
+        Some(dir) => paths.push(PathBuf::from(dir)),
+        None => paths.extend(home.as_ref().map(|h| h.join(".claude"))),
+    }
+    paths.extend(home.map(|h| h.join(".claude.json")));
+    paths
+}
+
+/// Enforce the ruleset on the current (child) process.
+fn restrict_self(ruleset: &OwnedFd) -> io::Result<()> {
+    // SAFETY: prctl and landlock_restrict_self take plain integer arguments.
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0u32) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Pre-formatted uid/gid maps that keep the caller's ids inside the new user namespace.
+struct IdMaps {
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+}
+
+impl IdMaps {
+    fn current() -> Self {
+        // SAFETY: getuid/getgid cannot fail.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Self {
+            uid_map: format!("{uid} {uid} 1").into_bytes(),
+            gid_map: format!("{gid} {gid} 1").into_bytes(),
+        }
+    }
+
+    /// Move into new user and network namespaces. Runs in the forked child.
+    fn enter_namespaces(&self) -> io::Result<()> {
+        // SAFETY: unshare takes plain flags; the child is single-threaded.
+        if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        write_proc_file(b"/proc/self/setgroups\0", b"deny")?;
+        write_proc_file(b"/proc/self/uid_map\0", &self.uid_map)?;
+        write_proc_file(b"/proc/self/gid_map\0", &self.gid_map)?;
+        Ok(())
+    }
+}
+
+/// open/write/close without allocating. `path` must be NUL-terminated.
+fn write_proc_file(path: &[u8], data: &[u8]) -> io::Result<()> {
+    debug_assert_eq!(path.last(), Some(&0));
+    // SAFETY: `path` is NUL-terminated and `data` is a valid buffer.
+    unsafe {
+        let fd = libc::open(path.as_ptr().cast(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let written = libc::write(fd, data.as_ptr().cast(), data.len());
+        let err = io::Error::last_os_error();
+        libc::close(fd);
+        if written != data.len() as isize {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
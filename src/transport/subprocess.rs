@@ -1,26 +1,76 @@
+use std::collections::VecDeque;
 use std::future::Future;
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 use serde_json::Value;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, Command};
-use tokio::sync::mpsc;
+use tokio::process::Command;
+use tokio::sync::{mpsc, watch};
 use tokio_util::sync::CancellationToken;
 
 use crate::error::{Error, Result};
+use crate::types::limits::ResourceLimits;
 use crate::types::options::{ClaudeAgentOptions, StderrCallback};
 use crate::types::permissions::PermissionMode;
+use crate::types::sandbox::{SandboxSettings, SandboxType};
 
-use super::{Transport, TransportWriter};
+use super::egress_proxy::EgressProxy;
+use super::{Transport, TransportChannels, TransportWriter};
+
+/// How long to wait for the CLI to exit on its own after stdin is closed.
+const INPUT_CLOSE_GRACE: Duration = Duration::from_secs(5);
+
+/// How long to wait for the CLI to exit after SIGTERM before sending SIGKILL.
+const TERMINATE_GRACE: Duration = Duration::from_secs(5);
+
+/// How long to wait for stderr to drain after the process exits.
+const STDERR_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Number of stderr lines kept for error reporting.
+const STDERR_TAIL_LINES: usize = 100;
 
 /// Transport implementation that communicates with the Claude CLI via subprocess.
 pub struct SubprocessTransport {
     cli_path: PathBuf,
     options: BuildOptions,
-    child: Option<Child>,
+    pid: Option<u32>,
+    exit_rx: Option<watch::Receiver<Option<ExitStatus>>>,
+    stderr_tail: StderrTail,
+    /// Closes stdin when cancelled.
+    input_closed: CancellationToken,
+    /// Tells the waiter task to SIGKILL the process.
+    kill: CancellationToken,
+    /// Set while `close()` is tearing the process down, so the exit it
+    /// causes isn't reported as an error.
+    closing: Arc<AtomicBool>,
     cancel: CancellationToken,
     ready: bool,
+    input_close_grace: Duration,
+    terminate_grace: Duration,
+}
+
+/// Bounded ring buffer of the most recent stderr lines.
+#[derive(Clone, Default)]
+struct StderrTail(Arc<std::sync::Mutex<VecDeque<String>>>);
+
+impl StderrTail {
+    fn push(&self, line: String) {
+        let mut lines = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if lines.len() == STDERR_TAIL_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    fn snapshot(&self) -> String {
+        let lines = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        lines.iter().map(String::as_str).collect::<Vec<_>>().join("\n")
+    }
 }
 
 /// Subset of ClaudeAgentOptions needed for building the CLI command.
@@ -31,16 +81,21 @@ struct BuildOptions {
     max_turns: Option<u32>,
     max_tokens: Option<u32>,
     session_id: Option<String>,
+    resume: Option<String>,
     continue_session: bool,
+    fork_session: bool,
     cwd: Option<PathBuf>,
     permission_mode: PermissionMode,
     allowed_tools: Vec<String>,
+    disallowed_tools: Vec<String>,
     no_cache: bool,
     temperature: Option<f64>,
     context_window: Option<f64>,
     extra_cli_args: Vec<String>,
     env: std::collections::HashMap<String, String>,
     on_stderr: Option<StderrCallback>,
+    resource_limits: Option<ResourceLimits>,
+    sandbox: Option<SandboxSettings>,
 }
 
 impl From<&ClaudeAgentOptions> for BuildOptions {
@@ -52,16 +107,21 @@ impl From<&ClaudeAgentOptions> for BuildOptions {
             max_turns: opts.max_turns,
             max_tokens: opts.max_tokens,
             session_id: opts.session_id.clone(),
+            resume: opts.resume.clone(),
             continue_session: opts.continue_session,
+            fork_session: opts.fork_session,
             cwd: opts.cwd.clone(),
-            permission_mode: opts.permission_mode.clone(),
+            permission_mode: opts.permission_mode,
             allowed_tools: opts.allowed_tools.clone(),
+            disallowed_tools: opts.disallowed_tools.clone(),
             no_cache: opts.no_cache,
             temperature: opts.temperature,
             context_window: opts.context_window,
             extra_cli_args: opts.extra_cli_args.clone(),
             env: opts.env.clone(),
             on_stderr: opts.on_stderr.clone(),
+            resource_limits: opts.resource_limits.clone(),
+            sandbox: opts.sandbox.clone(),
         }
     }
 }
@@ -71,14 +131,31 @@ impl SubprocessTransport {
         Self {
             cli_path,
             options: BuildOptions::from(options),
-            child: None,
+            pid: None,
+            exit_rx: None,
+            stderr_tail: StderrTail::default(),
+            input_closed: CancellationToken::new(),
+            kill: CancellationToken::new(),
+            closing: Arc::new(AtomicBool::new(false)),
             cancel: CancellationToken::new(),
             ready: false,
+            input_close_grace: INPUT_CLOSE_GRACE,
+            terminate_grace: TERMINATE_GRACE,
         }
     }
 
+    /// Exit code of the CLI process, once it has exited.
+    ///
+    /// `None` while running, or if the process was killed by a signal.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_rx
+            .as_ref()
+            .and_then(|rx| *rx.borrow())
+            .and_then(|status| status.code())
+    }
+
     /// Build the CLI command with all flags.
-    fn build_command(&self) -> Command {
+    fn build_command(&self) -> Result<Command> {
         let mut cmd = Command::new(&self.cli_path);
 
         cmd.args(["--output-format", "stream-json"]);
@@ -109,23 +186,26 @@ impl SubprocessTransport {
             cmd.args(["--session-id", sid]);
         }
 
+        if let Some(ref sid) = self.options.resume {
+            cmd.args(["--resume", sid]);
+        }
+
         if self.options.continue_session {
             cmd.arg("--continue");
         }
 
-        match &self.options.permission_mode {
-            PermissionMode::Default => {}
-            PermissionMode::AcceptAll => {
-                cmd.args(["--permission-mode", "bypassPermissions"]);
-            }
-            PermissionMode::DenyAll => {
-                cmd.args(["--permission-mode", "plan"]);
-            }
-            PermissionMode::AllowedTools => {
-                for tool in &self.options.allowed_tools {
-                    cmd.args(["--allowedTools", tool]);
-                }
-            }
+        if self.options.fork_session {
+            cmd.arg("--fork-session");
+        }
+
+        if self.options.permission_mode != PermissionMode::Default {
+            cmd.args(["--permission-mode", self.options.permission_mode.as_str()]);
+        }
+        for tool in &self.options.allowed_tools {
+            cmd.args(["--allowedTools", tool]);
+        }
+        for tool in &self.options.disallowed_tools {
+            cmd.args(["--disallowedTools", tool]);
         }
 
         if self.options.no_cache {
@@ -155,17 +235,57 @@ impl SubprocessTransport {
         cmd.stdin(std::process::Stdio::piped());
         cmd.stdout(std::process::Stdio::piped());
         cmd.stderr(std::process::Stdio::piped());
+        cmd.kill_on_drop(true);
+
+        // Run the CLI as leader of its own process group so that the tools,
+        // MCP servers and subagents it spawns can be signalled together.
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        #[cfg(unix)]
+        if let Some(limits) = self.options.resource_limits.clone() {
+            // SAFETY: the hook runs in the forked child before exec and only
+            // calls setrlimit(2), which is async-signal-safe.
+            unsafe {
+                cmd.pre_exec(move || apply_rlimits(&limits));
+            }
+        }
 
-        cmd
+        if let Some(ref sandbox) = self.options.sandbox {
+            match sandbox.sandbox_type {
+                None | Some(SandboxType::None) => {}
+                Some(SandboxType::Namespaces) => self.apply_namespace_sandbox(&mut cmd, sandbox)?,
+                Some(ref other) => {
+                    tracing::warn!(sandbox_type = ?other, "sandbox type not supported by subprocess transport, ignoring");
+                }
+            }
+        }
+
+        Ok(cmd)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn apply_namespace_sandbox(&self, cmd: &mut Command, sandbox: &SandboxSettings) -> Result<()> {
+        let cwd = match self.options.cwd {
+            Some(ref cwd) => cwd.clone(),
+            None => std::env::current_dir()?,
+        };
+        super::namespaces::apply(cmd, sandbox, &cwd, &self.cli_path, &self.options.env)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn apply_namespace_sandbox(&self, _cmd: &mut Command, _sandbox: &SandboxSettings) -> Result<()> {
+        Err(Error::Sandbox("namespace sandbox requires Linux".into()))
     }
 }
 
 impl Transport for SubprocessTransport {
-    fn connect(&mut self) -> Pin<Box<dyn Future<Output = Result<(mpsc::Receiver<Result<Value>>, TransportWriter)>> + Send + '_>> {
+    fn connect(&mut self) -> Pin<Box<dyn Future<Output = Result<TransportChannels>> + Send + '_>> {
         Box::pin(self.connect_impl())
     }
 
     fn end_input(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        self.input_closed.cancel();
         Box::pin(async { Ok(()) })
     }
 
@@ -176,18 +296,51 @@ impl Transport for SubprocessTransport {
     fn is_ready(&self) -> bool {
         self.ready
     }
+
+    fn exit_code(&self) -> Option<i32> {
+        SubprocessTransport::exit_code(self)
+    }
 }
 
 impl SubprocessTransport {
-    async fn connect_impl(&mut self) -> Result<(mpsc::Receiver<Result<Value>>, TransportWriter)> {
+    async fn connect_impl(&mut self) -> Result<TransportChannels> {
         if self.ready {
             return Err(Error::AlreadyConnected);
         }
 
-        let mut cmd = self.build_command();
-        let mut child = cmd
-            .spawn()
-            .map_err(|e| Error::CliConnection(format!("failed to spawn CLI: {e}")))?;
+        // Incoming message channel (stdout -> reader).
+        let (read_tx, read_rx) = mpsc::channel::<Result<Value>>(256);
+
+        // Cancelled when the stdout reader finishes, so helper tasks that
+        // hold a sender don't keep the incoming channel open.
+        let output_done = self.cancel.child_token();
+
+        let mut cmd = self.build_command()?;
+
+        let egress = self.options.sandbox.as_ref().and_then(|s| s.egress.clone());
+        if let Some(policy) = egress {
+            let proxy = EgressProxy::start(policy, read_tx.clone(), output_done.clone()).await?;
+            let url = proxy.url();
+            for var in [
+                "HTTPS_PROXY",
+                "https_proxy",
+                "HTTP_PROXY",
+                "http_proxy",
+                "ALL_PROXY",
+                "all_proxy",
+            ] {
+                cmd.env(var, &url);
+            }
+            // An inherited bypass list would route hosts around the policy.
+            for var in ["NO_PROXY", "no_proxy"] {
+                cmd.env_remove(var);
+            }
+        }
+
+        let mut child = cmd.spawn().map_err(|e| {
+            output_done.cancel();
+            Error::CliConnection(format!("failed to spawn CLI: {e}"))
+        })?;
 
         let stdout = child
             .stdout
@@ -202,21 +355,52 @@ impl SubprocessTransport {
             .take()
             .ok_or_else(|| Error::CliConnection("no stdin".into()))?;
 
-        self.child = Some(child);
+        self.pid = child.id();
         self.ready = true;
 
-        // Incoming message channel (stdout -> reader).
-        let (read_tx, read_rx) = mpsc::channel::<Result<Value>>(256);
+        // Waiter task: owns the child, reaps it and publishes its exit status.
+        let (exit_tx, exit_rx) = watch::channel(None);
+        self.exit_rx = Some(exit_rx.clone());
+        let kill = self.kill.clone();
+        let pid = self.pid;
+        tokio::spawn(async move {
+            let status = tokio::select! {
+                status = child.wait() => status,
+                _ = kill.cancelled() => {
+                    if let Some(pid) = pid {
+                        kill_group(pid);
+                    }
+                    let _ = child.start_kill();
+                    child.wait().await
+                }
+            };
+            match status {
+                Ok(status) => {
+                    let _ = exit_tx.send(Some(status));
+                }
+                Err(e) => tracing::error!("failed to wait for CLI process: {e}"),
+            }
+        });
 
         // Outgoing message channel (writer -> stdin).
         let (write_tx, mut write_rx) = mpsc::channel::<Value>(256);
 
         let cancel = self.cancel.clone();
 
+        let stderr_done = CancellationToken::new();
+
+        let limit_tx = read_tx.clone();
+
         // Stdout reader task.
         let stdout_tx = read_tx;
         let stdout_cancel = cancel.clone();
+        let mut stdout_exit_rx = exit_rx;
+        let stdout_stderr_done = stderr_done.clone();
+        let stdout_stderr_tail = self.stderr_tail.clone();
+        let closing = self.closing.clone();
+        let stdout_done = output_done.clone();
         tokio::spawn(async move {
+            let _done = stdout_done.drop_guard();
             let reader = BufReader::new(stdout);
             let mut lines = reader.lines();
 
@@ -244,21 +428,42 @@ impl SubprocessTransport {
                             Ok(None) => break,
                             Err(e) => {
                                 let _ = stdout_tx.send(Err(Error::Io(e))).await;
-                                break;
+                                return;
                             }
                         }
                     }
                 }
             }
+
+            // Stdout closed: report an abnormal exit unless we caused it.
+            let status = tokio::select! {
+                _ = stdout_cancel.cancelled() => return,
+                status = wait_for_exit(&mut stdout_exit_rx) => status,
+            };
+            if closing.load(Ordering::SeqCst) {
+                return;
+            }
+            if let Some(status) = status.filter(|s| !s.success()) {
+                let _ = tokio::time::timeout(STDERR_DRAIN_TIMEOUT, stdout_stderr_done.cancelled()).await;
+                let _ = stdout_tx
+                    .send(Err(Error::ProcessExit {
+                        code: status.code().unwrap_or(-1),
+                        stderr: stdout_stderr_tail.snapshot(),
+                    }))
+                    .await;
+            }
         });
 
         // Stdin writer task: reads from write channel, serializes to stdin.
+        // Dropping stdin on exit closes the pipe, signalling EOF to the CLI.
         let write_cancel = cancel.clone();
+        let input_closed = self.input_closed.clone();
         tokio::spawn(async move {
             let mut stdin = stdin;
             loop {
                 tokio::select! {
                     _ = write_cancel.cancelled() => break,
+                    _ = input_closed.cancelled() => break,
                     msg = write_rx.recv() => {
                         match msg {
                             Some(value) => {
@@ -289,6 +494,7 @@ impl SubprocessTransport {
 
         // Stderr reader task.
         let on_stderr = self.options.on_stderr.clone();
+        let stderr_tail = self.stderr_tail.clone();
         let stderr_cancel = cancel;
         tokio::spawn(async move {
             let reader = BufReader::new(stderr);
@@ -300,6 +506,7 @@ impl SubprocessTransport {
                     line = lines.next_line() => {
                         match line {
                             Ok(Some(line)) => {
+                                stderr_tail.push(line.clone());
                                 if let Some(ref cb) = on_stderr {
                                     cb(line);
                                 } else {
@@ -311,27 +518,553 @@ impl SubprocessTransport {
                     }
                 }
             }
+            stderr_done.cancel();
         });
 
+        // Wall-clock limit task: reports the overrun, then kills the process group.
+        if let Some(limit) = self.options.resource_limits.as_ref().and_then(|l| l.wall_clock) {
+            let limit_cancel = output_done;
+            let kill = self.kill.clone();
+            let closing = self.closing.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = limit_cancel.cancelled() => {}
+                    _ = tokio::time::sleep(limit) => {
+                        tracing::warn!(?limit, "CLI exceeded wall-clock limit, killing it");
+                        closing.store(true, Ordering::SeqCst);
+                        let _ = limit_tx.send(Err(Error::WallClockLimitExceeded(limit))).await;
+                        kill.cancel();
+                    }
+                }
+            });
+        }
+
         let writer = TransportWriter::new(write_tx);
         Ok((read_rx, writer))
     }
 
+    /// Staged shutdown: close stdin, wait, SIGTERM, wait, SIGKILL.
+    ///
+    /// Signals go to the CLI's whole process group. Once the CLI itself has
+    /// exited, anything left in its group is killed.
     async fn close_impl(&mut self) -> Result<()> {
         self.ready = false;
-        self.cancel.cancel();
+        self.closing.store(true, Ordering::SeqCst);
+        self.input_closed.cancel();
+
+        if let Some(mut exit_rx) = self.exit_rx.clone() {
+            let exited = tokio::time::timeout(self.input_close_grace, wait_for_exit(&mut exit_rx))
+                .await
+                .is_ok();
 
-        if let Some(ref mut child) = self.child {
-            let _ = child.kill().await;
+            if !exited {
+                tracing::debug!(pid = ?self.pid, "CLI did not exit after stdin closed, sending SIGTERM");
+                if let Some(pid) = self.pid {
+                    terminate_group(pid);
+                }
+                let terminated = tokio::time::timeout(self.terminate_grace, wait_for_exit(&mut exit_rx))
+                    .await
+                    .is_ok();
+
+                if !terminated {
+                    tracing::debug!(pid = ?self.pid, "CLI did not exit after SIGTERM, sending SIGKILL");
+                    self.kill.cancel();
+                    wait_for_exit(&mut exit_rx).await;
+                }
+            }
+
+            if let Some(pid) = self.pid {
+                kill_group(pid);
+            }
         }
 
-        self.child = None;
+        self.cancel.cancel();
         Ok(())
     }
 }
 
+/// Wait until the waiter task publishes an exit status.
+///
+/// Returns `None` if the waiter task failed to reap the process.
+async fn wait_for_exit(exit_rx: &mut watch::Receiver<Option<ExitStatus>>) -> Option<ExitStatus> {
+    exit_rx.wait_for(Option::is_some).await.ok().and_then(|status| *status)
+}
+
+/// Apply rlimits in the forked child. Must stay async-signal-safe.
+#[cfg(unix)]
+fn apply_rlimits(limits: &ResourceLimits) -> std::io::Result<()> {
+    let set = |resource, value: Option<u64>| -> std::io::Result<()> {
+        if let Some(value) = value {
+            let rlim = libc::rlimit {
+                rlim_cur: value as libc::rlim_t,
+                rlim_max: value as libc::rlim_t,
+            };
+            // SAFETY: `rlim` is a valid, initialized rlimit struct.
+            if unsafe { libc::setrlimit(resource, &rlim) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    };
+
+    set(libc::RLIMIT_AS, limits.address_space_bytes)?;
+    set(libc::RLIMIT_CPU, limits.cpu_seconds)?;
+    set(libc::RLIMIT_NOFILE, limits.open_files)?;
+    set(libc::RLIMIT_NPROC, limits.max_processes)?;
+    Ok(())
+}
+
+/// Ask the process group led by `pid` to terminate (SIGTERM).
+#[cfg(unix)]
+fn terminate_group(pid: u32) {
+    signal_group(pid, libc::SIGTERM);
+}
+
+/// Kill the process group led by `pid` (SIGKILL).
+#[cfg(unix)]
+fn kill_group(pid: u32) {
+    signal_group(pid, libc::SIGKILL);
+}
+
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: libc::c_int) {
+    // SAFETY: kill(2) has no memory-safety preconditions. A negative pid
+    // addresses the process group; ESRCH (group already gone) is ignored.
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), signal);
+    }
+}
+
+/// No SIGTERM equivalent off unix; the caller falls through to a hard kill.
+#[cfg(not(unix))]
+fn terminate_group(_pid: u32) {}
+
+/// Off unix only the direct child is killed (via `kill_on_drop`/`start_kill`).
+#[cfg(not(unix))]
+fn kill_group(_pid: u32) {}
+
 impl Drop for SubprocessTransport {
     fn drop(&mut self) {
+        // The waiter task may never run again if the runtime is shutting
+        // down, so signal the group synchronously while we still can.
+        if self.ready {
+            if let Some(pid) = self.pid {
+                kill_group(pid);
+            }
+        }
+        self.kill.cancel();
         self.cancel.cancel();
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+
+    /// Write an executable shell script standing in for the CLI.
+    fn fake_cli(dir: &tempfile::TempDir, body: &str) -> PathBuf {
+        let path = dir.path().join("claude");
+        std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn permission_flags() {
+        let options = ClaudeAgentOptions {
+            permission_mode: PermissionMode::AcceptEdits,
+            allowed_tools: vec!["Read".into(), "Bash(git status:*)".into()],
+            disallowed_tools: vec!["WebFetch".into()],
+            ..Default::default()
+        };
+        let transport = SubprocessTransport::new("claude".into(), &options);
+        let cmd = transport.build_command().unwrap();
+        let args: Vec<_> = cmd.as_std().get_args().filter_map(|a| a.to_str()).collect();
+        let flag = |name: &str| -> Vec<&str> {
+            args.windows(2).filter(|w| w[0] == name).map(|w| w[1]).collect()
+        };
+
+        assert_eq!(flag("--permission-mode"), ["acceptEdits"]);
+        assert_eq!(flag("--allowedTools"), ["Read", "Bash(git status:*)"]);
+        assert_eq!(flag("--disallowedTools"), ["WebFetch"]);
+
+        let transport = SubprocessTransport::new("claude".into(), &ClaudeAgentOptions::default());
+        let cmd = transport.build_command().unwrap();
+        assert!(!cmd.as_std().get_args().any(|a| a == "--permission-mode"));
+    }
+
+    #[test]
+    fn session_flags() {
+        let options = ClaudeAgentOptions {
+            resume: Some("sess_1".into()),
+            fork_session: true,
+            ..Default::default()
+        };
+        let transport = SubprocessTransport::new("claude".into(), &options);
+        let cmd = transport.build_command().unwrap();
+        let args: Vec<_> = cmd.as_std().get_args().filter_map(|a| a.to_str()).collect();
+        assert!(args.windows(2).any(|w| w == ["--resume", "sess_1"]));
+        assert!(args.contains(&"--fork-session"));
+        assert!(!args.contains(&"--session-id"));
+        assert!(!args.contains(&"--continue"));
+
+        let options = ClaudeAgentOptions {
+            session_id: Some("sess_2".into()),
+            ..Default::default()
+        };
+        let transport = SubprocessTransport::new("claude".into(), &options);
+        let cmd = transport.build_command().unwrap();
+        let args: Vec<_> = cmd.as_std().get_args().filter_map(|a| a.to_str()).collect();
+        assert!(args.windows(2).any(|w| w == ["--session-id", "sess_2"]));
+        assert!(!args.contains(&"--resume"));
+    }
+
+    #[tokio::test]
+    async fn abnormal_exit_is_reported_with_stderr_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let cli = fake_cli(&dir, "echo 'auth failed' >&2\nexit 3");
+        let mut transport = SubprocessTransport::new(cli, &ClaudeAgentOptions::default());
+
+        let (mut rx, _writer) = transport.connect().await.unwrap();
+        match rx.recv().await {
+            Some(Err(Error::ProcessExit { code, stderr })) => {
+                assert_eq!(code, 3);
+                assert_eq!(stderr, "auth failed");
+            }
+            other => panic!("expected ProcessExit, got {other:?}"),
+        }
+        assert_eq!(transport.exit_code(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn close_ends_input_and_reaps_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let cli = fake_cli(&dir, "cat >/dev/null\nexit 0");
+        let mut transport = SubprocessTransport::new(cli, &ClaudeAgentOptions::default());
+
+        let (_rx, _writer) = transport.connect().await.unwrap();
+        transport.close().await.unwrap();
+        assert_eq!(transport.exit_code(), Some(0));
+    }
+
+    /// Whether `pid` is a live (non-zombie) process.
+    #[cfg(target_os = "linux")]
+    fn is_alive(pid: u32) -> bool {
+        match std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+            // The state field follows the parenthesized command name.
+            Ok(stat) => !stat.rsplit_once(") ").is_some_and(|(_, rest)| rest.starts_with('Z')),
+            Err(_) => false,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn read_pid(path: &std::path::Path) -> u32 {
+        for _ in 0..50 {
+            if let Ok(pid) = std::fs::read_to_string(path) {
+                if let Ok(pid) = pid.trim().parse() {
+                    return pid;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("fake CLI never wrote {}", path.display());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn close_kills_process_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let pidfile = dir.path().join("grandchild.pid");
+        let cli = fake_cli(
+            &dir,
+            &format!("sleep 30 &\necho $! > {}\ncat >/dev/null", pidfile.display()),
+        );
+        let mut transport = SubprocessTransport::new(cli, &ClaudeAgentOptions::default());
+
+        let (_rx, _writer) = transport.connect().await.unwrap();
+        let grandchild = read_pid(&pidfile).await;
+        assert!(is_alive(grandchild));
+
+        transport.close().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!is_alive(grandchild));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn drop_kills_process_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let pidfile = dir.path().join("grandchild.pid");
+        let cli = fake_cli(
+            &dir,
+            &format!("sleep 30 &\necho $! > {}\ncat >/dev/null", pidfile.display()),
+        );
+        let mut transport = SubprocessTransport::new(cli, &ClaudeAgentOptions::default());
+
+        let (_rx, _writer) = transport.connect().await.unwrap();
+        let grandchild = read_pid(&pidfile).await;
+        drop(transport);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!is_alive(grandchild));
+    }
+
+    #[tokio::test]
+    async fn rlimits_are_applied_to_child() {
+        let dir = tempfile::tempdir().unwrap();
+        let cli = fake_cli(&dir, "echo \"{\\\"nofile\\\": $(ulimit -n)}\"");
+        let options = ClaudeAgentOptions {
+            resource_limits: Some(ResourceLimits {
+                open_files: Some(64),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut transport = SubprocessTransport::new(cli, &options);
+
+        let (mut rx, _writer) = transport.connect().await.unwrap();
+        let value = rx.recv().await.unwrap().unwrap();
+        assert_eq!(value["nofile"], 64);
+    }
+
+    #[tokio::test]
+    async fn wall_clock_limit_tears_down_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let cli = fake_cli(&dir, "cat >/dev/null");
+        let options = ClaudeAgentOptions {
+            resource_limits: Some(ResourceLimits {
+                wall_clock: Some(Duration::from_millis(200)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut transport = SubprocessTransport::new(cli, &options);
+
+        let (mut rx, _writer) = transport.connect().await.unwrap();
+        match rx.recv().await {
+            Some(Err(Error::WallClockLimitExceeded(limit))) => {
+                assert_eq!(limit, Duration::from_millis(200));
+            }
+            other => panic!("expected WallClockLimitExceeded, got {other:?}"),
+        }
+        let mut exit_rx = transport.exit_rx.clone().unwrap();
+        let status = wait_for_exit(&mut exit_rx).await.unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    fn namespace_sandbox(cwd: &std::path::Path, settings: SandboxSettings) -> ClaudeAgentOptions {
+        ClaudeAgentOptions {
+            cwd: Some(cwd.to_path_buf()),
+            sandbox: Some(SandboxSettings {
+                sandbox_type: Some(SandboxType::Namespaces),
+                ..settings
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn namespace_sandbox_confines_filesystem() {
+        if super::super::namespaces::landlock_abi().is_none() {
+            eprintln!("skipping: Landlock unavailable");
+            return;
+        }
+        let cli_dir = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), "s3cret").unwrap();
+        std::fs::write(shared.path().join("docs"), "readable").unwrap();
+
+        let script = format!(
+            r#"try() {{ if "$@" >/dev/null 2>&1; then echo ok; else echo denied; fi; }}
+echo "{{\"inside\": \"$(try touch inside.txt)\", \"outside\": \"$(try touch {out}/x)\", \"read_outside\": \"$(try cat {out}/secret)\", \"read_shared\": \"$(try cat {shared}/docs)\", \"write_shared\": \"$(try touch {shared}/x)\"}}""#,
+            out = outside.path().display(),
+            shared = shared.path().display(),
+        );
+        let cli = fake_cli(&cli_dir, &script);
+        let options = namespace_sandbox(
+            workspace.path(),
+            SandboxSettings {
+                allow_network: true,
+                read_only_paths: vec![shared.path().to_path_buf()],
+                // The test dirs live in the temp dir.
+                writable_tmp: false,
+                ..Default::default()
+            },
+        );
+        let mut transport = SubprocessTransport::new(cli, &options);
+
+        let (mut rx, _writer) = transport.connect().await.unwrap();
+        let value = rx.recv().await.unwrap().unwrap();
+        assert_eq!(value["inside"], "ok");
+        assert_eq!(value["outside"], "denied");
+        assert_eq!(value["read_outside"], "denied");
+        assert_eq!(value["read_shared"], "ok");
+        assert_eq!(value["write_shared"], "denied");
+        assert!(workspace.path().join("inside.txt").exists());
+        assert!(!outside.path().join("x").exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn namespace_sandbox_home_access_is_limited_to_cli_state() {
+        if super::super::namespaces::landlock_abi().is_none() {
+            eprintln!("skipping: Landlock unavailable");
+            return;
+        }
+        let cli_dir = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let home = tempfile::tempdir().unwrap();
+        std::fs::create_dir(home.path().join(".ssh")).unwrap();
+        std::fs::write(home.path().join(".ssh/id_ed25519"), "key").unwrap();
+        std::fs::create_dir(home.path().join(".claude")).unwrap();
+
+        let script = r#"try() { if "$@" >/dev/null 2>&1; then echo ok; else echo denied; fi; }
+echo "{\"ssh\": \"$(try cat "$HOME/.ssh/id_ed25519")\", \"state\": \"$(try touch "$HOME/.claude/x")\"}""#;
+        let cli = fake_cli(&cli_dir, script);
+        let mut options = namespace_sandbox(
+            workspace.path(),
+            SandboxSettings {
+                allow_network: true,
+                // The test dirs live in the temp dir.
+                writable_tmp: false,
+                home_access: true,
+                ..Default::default()
+            },
+        );
+        options
+            .env
+            .insert("HOME".into(), home.path().display().to_string());
+        let mut transport = SubprocessTransport::new(cli, &options);
+
+        let (mut rx, _writer) = transport.connect().await.unwrap();
+        let value = rx.recv().await.unwrap().unwrap();
+        assert_eq!(value["ssh"], "denied");
+        assert_eq!(value["state"], "ok");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn namespace_sandbox_allows_temp_dir_by_default() {
+        if super::super::namespaces::landlock_abi().is_none() {
+            eprintln!("skipping: Landlock unavailable");
+            return;
+        }
+        let cli_dir = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let scratch = tempfile::tempdir().unwrap();
+        let script = format!(
+            r#"if touch {}/x 2>/dev/null; then echo '{{"tmp": "ok"}}'; else echo '{{"tmp": "denied"}}'; fi"#,
+            scratch.path().display()
+        );
+        let cli = fake_cli(&cli_dir, &script);
+        let options = namespace_sandbox(workspace.path(), SandboxSettings::default());
+        let mut transport = SubprocessTransport::new(cli, &options);
+
+        let (mut rx, _writer) = transport.connect().await.unwrap();
+        let value = rx.recv().await.unwrap().unwrap();
+        assert_eq!(value["tmp"], "ok");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn namespace_sandbox_rejects_egress_without_network() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = namespace_sandbox(
+            dir.path(),
+            SandboxSettings {
+                allow_network: false,
+                egress: Some(crate::types::sandbox::EgressPolicy::default()),
+                ..Default::default()
+            },
+        );
+        let mut transport = SubprocessTransport::new(fake_cli(&dir, "true"), &options);
+        assert!(matches!(
+            transport.connect().await,
+            Err(Error::Sandbox(e)) if e.contains("allow_network")
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn namespace_sandbox_isolates_network() {
+        if super::super::namespaces::landlock_abi().is_none() {
+            eprintln!("skipping: Landlock unavailable");
+            return;
+        }
+        let cli_dir = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let cli = fake_cli(
+            &cli_dir,
+            r#"echo "{\"ifaces\": \"$(tail -n +3 /proc/net/dev | cut -d: -f1 | tr -d ' \n')\"}""#,
+        );
+        let options = namespace_sandbox(
+            workspace.path(),
+            SandboxSettings {
+                allow_network: false,
+                ..Default::default()
+            },
+        );
+        let mut transport = SubprocessTransport::new(cli, &options);
+
+        let (mut rx, _writer) = match transport.connect().await {
+            Ok(channels) => channels,
+            Err(Error::CliConnection(e)) if e.contains("Operation not permitted") => {
+                eprintln!("skipping: user namespaces unavailable");
+                return;
+            }
+            Err(e) => panic!("connect failed: {e}"),
+        };
+        let value = rx.recv().await.unwrap().unwrap();
+        assert_eq!(value["ifaces"], "lo");
+    }
+
+    #[tokio::test]
+    async fn egress_proxy_is_exported_to_cli() {
+        let dir = tempfile::tempdir().unwrap();
+        let cli = fake_cli(
+            &dir,
+            r#"echo "{\"proxy\": \"$HTTPS_PROXY\", \"all\": \"$ALL_PROXY\", \"no_proxy\": \"${NO_PROXY-unset}${no_proxy-unset}\"}""#,
+        );
+        let options = ClaudeAgentOptions {
+            sandbox: Some(SandboxSettings {
+                egress: Some(crate::types::sandbox::EgressPolicy::default()),
+                ..Default::default()
+            }),
+            env: [("NO_PROXY".to_string(), "*".to_string())].into(),
+            ..Default::default()
+        };
+        let mut transport = SubprocessTransport::new(cli, &options);
+
+        let (mut rx, _writer) = transport.connect().await.unwrap();
+        let value = rx.recv().await.unwrap().unwrap();
+        assert!(value["proxy"].as_str().unwrap().starts_with("http://127.0.0.1:"));
+        assert_eq!(value["all"], value["proxy"]);
+        assert_eq!(value["no_proxy"], "unsetunset");
+        // The proxy must not keep the channel open once the CLI is done.
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn close_escalates_to_sigkill() {
+        let dir = tempfile::tempdir().unwrap();
+        let cli = fake_cli(&dir, "trap '' TERM\nwhile true; do sleep 1; done");
+        let mut transport = SubprocessTransport::new(cli, &ClaudeAgentOptions::default());
+        transport.input_close_grace = Duration::from_millis(100);
+        transport.terminate_grace = Duration::from_millis(100);
+
+        let (_rx, _writer) = transport.connect().await.unwrap();
+        // Give the shell time to install its trap.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        transport.close().await.unwrap();
+
+        let status = transport.exit_rx.as_ref().and_then(|rx| *rx.borrow()).unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+    }
+}
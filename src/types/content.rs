@@ -1,6 +1,12 @@
+use std::path::Path;
+
+use base64::Engine as _;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::error::{Error, Result};
+use crate::types::tools::{parse_tool_input, BuiltinToolInput};
+
 /// A block of content within a message.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -25,6 +31,14 @@ pub enum ContentBlock {
         #[serde(default)]
         is_error: bool,
     },
+    Image {
+        source: ImageSource,
+    },
+    Document {
+        source: DocumentSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+    },
 }
 
 /// Content of a tool result - can be a simple string or structured blocks.
@@ -50,7 +64,91 @@ pub struct ImageSource {
     pub data: String,
 }
 
+/// Source of a document block: base64 PDF data or inline plain text.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DocumentSource {
+    #[serde(rename = "type")]
+    pub source_type: String,
+    pub media_type: String,
+    pub data: String,
+}
+
 impl ContentBlock {
+    /// Create a text block.
+    pub fn text(text: impl Into<String>) -> Self {
+        ContentBlock::Text { text: text.into() }
+    }
+
+    /// Create an image block from base64-encoded data.
+    pub fn image_base64(media_type: impl Into<String>, data: impl Into<String>) -> Self {
+        ContentBlock::Image {
+            source: ImageSource {
+                source_type: "base64".into(),
+                media_type: media_type.into(),
+                data: data.into(),
+            },
+        }
+    }
+
+    /// Create an image block from a file on disk.
+    ///
+    /// The media type is detected from the file's magic bytes. Supports PNG,
+    /// JPEG, GIF and WebP.
+    pub fn image_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let media_type = detect_image_media_type(&bytes)
+            .ok_or_else(|| Error::UnsupportedMediaType(path.display().to_string()))?;
+        Ok(Self::image_base64(media_type, BASE64.encode(&bytes)))
+    }
+
+    /// Create a PDF document block from base64-encoded data.
+    pub fn pdf_base64(data: impl Into<String>) -> Self {
+        ContentBlock::Document {
+            source: DocumentSource {
+                source_type: "base64".into(),
+                media_type: "application/pdf".into(),
+                data: data.into(),
+            },
+            title: None,
+        }
+    }
+
+    /// Create a plain-text document block.
+    pub fn text_document(text: impl Into<String>) -> Self {
+        ContentBlock::Document {
+            source: DocumentSource {
+                source_type: "text".into(),
+                media_type: "text/plain".into(),
+                data: text.into(),
+            },
+            title: None,
+        }
+    }
+
+    /// Create a document block from a file on disk.
+    ///
+    /// PDFs, recognized by their `%PDF-` header, are sent as base64; `.txt`
+    /// and `.md` files are sent as plain text. The file name is used as the
+    /// document title.
+    pub fn document_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let mut block = if bytes.starts_with(b"%PDF-") {
+            Self::pdf_base64(BASE64.encode(&bytes))
+        } else if has_extension(path, &["txt", "md"]) {
+            let text = String::from_utf8(bytes)
+                .map_err(|_| Error::UnsupportedMediaType(path.display().to_string()))?;
+            Self::text_document(text)
+        } else {
+            return Err(Error::UnsupportedMediaType(path.display().to_string()));
+        };
+        if let ContentBlock::Document { title, .. } = &mut block {
+            *title = path.file_name().map(|n| n.to_string_lossy().into_owned());
+        }
+        Ok(block)
+    }
+
     /// Extract text content if this is a Text block.
     pub fn as_text(&self) -> Option<&str> {
         match self {
@@ -66,6 +164,39 @@ impl ContentBlock {
             _ => None,
         }
     }
+
+    /// Typed tool input if this is a ToolUse block.
+    pub fn tool_input(&self) -> Option<BuiltinToolInput> {
+        match self {
+            ContentBlock::ToolUse { name, input, .. } => Some(parse_tool_input(name, input)),
+            _ => None,
+        }
+    }
+}
+
+const BASE64: base64::engine::GeneralPurpose = base64::engine::general_purpose::STANDARD;
+
+/// Detect an image media type from magic bytes.
+fn detect_image_media_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some("image/png");
+    }
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some("image/jpeg");
+    }
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        return Some("image/gif");
+    }
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    None
+}
+
+fn has_extension(path: &Path, exts: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| exts.iter().any(|x| e.eq_ignore_ascii_case(x)))
 }
 
 #[cfg(test)]
@@ -121,4 +252,65 @@ mod tests {
         let back: ContentBlock = serde_json::from_str(&json).unwrap();
         assert_eq!(block, back);
     }
+
+    #[test]
+    fn image_file_detects_media_type() {
+        let dir = tempfile::tempdir().unwrap();
+        // Magic bytes win over a misleading extension.
+        let path = dir.path().join("shot.jpg");
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\nrest").unwrap();
+
+        let block = ContentBlock::image_file(&path).unwrap();
+        let json = serde_json::to_value(&block).unwrap();
+        assert_eq!(json["type"], "image");
+        assert_eq!(json["source"]["type"], "base64");
+        assert_eq!(json["source"]["media_type"], "image/png");
+        assert_eq!(json["source"]["data"], BASE64.encode(b"\x89PNG\r\n\x1a\nrest"));
+    }
+
+    #[test]
+    fn image_file_rejects_unknown_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.bin");
+        std::fs::write(&path, b"plain bytes").unwrap();
+        assert!(matches!(
+            ContentBlock::image_file(&path),
+            Err(Error::UnsupportedMediaType(_))
+        ));
+        // The extension alone doesn't make it an image.
+        let path = dir.path().join("notes.png");
+        std::fs::write(&path, b"plain bytes").unwrap();
+        assert!(matches!(
+            ContentBlock::image_file(&path),
+            Err(Error::UnsupportedMediaType(_))
+        ));
+    }
+
+    #[test]
+    fn document_file_pdf_and_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let pdf = dir.path().join("report.pdf");
+        std::fs::write(&pdf, b"%PDF-1.7 ...").unwrap();
+        let json = serde_json::to_value(ContentBlock::document_file(&pdf).unwrap()).unwrap();
+        assert_eq!(json["type"], "document");
+        assert_eq!(json["source"]["media_type"], "application/pdf");
+        assert_eq!(json["title"], "report.pdf");
+
+        let txt = dir.path().join("notes.md");
+        std::fs::write(&txt, "# Notes").unwrap();
+        let json = serde_json::to_value(ContentBlock::document_file(&txt).unwrap()).unwrap();
+        assert_eq!(json["source"]["type"], "text");
+        assert_eq!(json["source"]["data"], "# Notes");
+    }
+
+    #[test]
+    fn document_file_requires_pdf_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let fake = dir.path().join("fake.pdf");
+        std::fs::write(&fake, b"<html>not a pdf</html>").unwrap();
+        assert!(matches!(
+            ContentBlock::document_file(&fake),
+            Err(Error::UnsupportedMediaType(_))
+        ));
+    }
 }
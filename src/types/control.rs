@@ -1,11 +1,13 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::permissions::PermissionMode;
+
 /// A control command sent from the SDK to the CLI.
 #[derive(Debug, Clone)]
 pub enum SDKControlCommand {
     Interrupt,
-    SetPermissionMode { mode: String },
+    SetPermissionMode { mode: PermissionMode },
     SetModel { model: String },
     RewindFiles { user_message_id: String },
     GetMcpStatus,
@@ -16,8 +18,8 @@ impl SDKControlCommand {
         Self::Interrupt
     }
 
-    pub fn set_permission_mode(mode: &str) -> Self {
-        Self::SetPermissionMode { mode: mode.into() }
+    pub fn set_permission_mode(mode: PermissionMode) -> Self {
+        Self::SetPermissionMode { mode }
     }
 
     pub fn set_model(model: &str) -> Self {
@@ -36,7 +38,7 @@ impl SDKControlCommand {
     pub fn to_request_body(&self) -> Value {
         match self {
             Self::Interrupt => serde_json::json!({"subtype": "interrupt"}),
-            Self::SetPermissionMode { mode } => serde_json::json!({"subtype": "set_permission_mode", "mode": mode}),
+            Self::SetPermissionMode { mode } => serde_json::json!({"subtype": "set_permission_mode", "mode": mode.as_str()}),
             Self::SetModel { model } => serde_json::json!({"subtype": "set_model", "model": model}),
             Self::RewindFiles { user_message_id } => serde_json::json!({"subtype": "rewind_files", "user_message_id": user_message_id}),
             Self::GetMcpStatus => serde_json::json!({"subtype": "get_mcp_status"}),
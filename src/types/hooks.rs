@@ -1,8 +1,13 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::types::tools::{parse_tool_input, BuiltinToolInput};
 
 /// Hook events that can be intercepted.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -18,6 +23,14 @@ pub enum HookEvent {
     Stop,
     #[serde(rename = "subagentStop")]
     SubagentStop,
+    #[serde(rename = "userPromptSubmit")]
+    UserPromptSubmit,
+    #[serde(rename = "preCompact")]
+    PreCompact,
+    #[serde(rename = "sessionStart")]
+    SessionStart,
+    #[serde(rename = "sessionEnd")]
+    SessionEnd,
 }
 
 impl std::fmt::Display for HookEvent {
@@ -34,51 +47,305 @@ impl HookEvent {
             Self::Notification => "Notification",
             Self::Stop => "Stop",
             Self::SubagentStop => "SubagentStop",
+            Self::UserPromptSubmit => "UserPromptSubmit",
+            Self::PreCompact => "PreCompact",
+            Self::SessionStart => "SessionStart",
+            Self::SessionEnd => "SessionEnd",
         }
     }
 }
 
 /// Matcher for which tool/event a hook applies to.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Only PreToolUse and PostToolUse hooks are filtered; other events always
+/// match. Matchers are sent to the CLI and also evaluated locally, so a
+/// callback only runs when its matcher matches.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HookMatcher {
+    /// Tool name pattern; `None` matches every tool.
+    ///
+    /// Alternatives are separated by a top-level `|` (`Edit|Write|MultiEdit`);
+    /// a `|` inside a group or character class belongs to that alternative.
+    /// Each one is an exact name, a glob using `*` (`mcp__github__*`), or, if
+    /// it contains other regex syntax, a regular expression
+    /// (`mcp__(docs|wiki)__search`).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tool_name: Option<String>,
+
+    /// Conditions on the tool input; all must hold.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub input: Vec<InputMatcher>,
+}
+
+/// A condition on a top-level string field of the tool input.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputMatcher {
+    /// The field starts with `prefix`, ignoring leading whitespace.
+    Prefix { field: String, prefix: String },
+    /// The field matches the regular expression `pattern`.
+    Regex { field: String, pattern: String },
+}
+
+impl HookMatcher {
+    /// Match tools by name pattern.
+    #[must_use]
+    pub fn tool(pattern: impl Into<String>) -> Self {
+        Self {
+            tool_name: Some(pattern.into()),
+            input: Vec::new(),
+        }
+    }
+
+    /// Also require the input `field` to start with `prefix`
+    /// (e.g. `HookMatcher::tool("Bash").with_input_prefix("command", "git push")`).
+    #[must_use]
+    pub fn with_input_prefix(mut self, field: impl Into<String>, prefix: impl Into<String>) -> Self {
+        self.input.push(InputMatcher::Prefix {
+            field: field.into(),
+            prefix: prefix.into(),
+        });
+        self
+    }
+
+    /// Also require the input `field` to match a regular expression.
+    #[must_use]
+    pub fn with_input_regex(mut self, field: impl Into<String>, pattern: impl Into<String>) -> Self {
+        self.input.push(InputMatcher::Regex {
+            field: field.into(),
+            pattern: pattern.into(),
+        });
+        self
+    }
+
+    /// The tool pattern as an anchored regex, in the form sent to the CLI.
+    pub(crate) fn tool_regex(&self) -> Option<String> {
+        self.tool_name.as_deref().map(tool_pattern_to_regex)
+    }
+
+    /// Compile the patterns for local evaluation.
+    pub(crate) fn compile(&self) -> Result<CompiledMatcher> {
+        let compile = |pattern: &str| {
+            Regex::new(pattern)
+                .map_err(|e| Error::Hook(format!("invalid hook matcher {pattern:?}: {e}")))
+        };
+
+        let tool_name = self.tool_regex().as_deref().map(compile).transpose()?;
+        let input = self
+            .input
+            .iter()
+            .map(|m| {
+                Ok(match m {
+                    InputMatcher::Prefix { field, prefix } => CompiledInputMatcher::Prefix {
+                        field: field.clone(),
+                        prefix: prefix.clone(),
+                    },
+                    InputMatcher::Regex { field, pattern } => CompiledInputMatcher::Regex {
+                        field: field.clone(),
+                        regex: compile(pattern)?,
+                    },
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(CompiledMatcher { tool_name, input })
+    }
+}
+
+/// Translate a tool pattern into an anchored regex.
+fn tool_pattern_to_regex(pattern: &str) -> String {
+    const REGEX_SYNTAX: &[char] = &['.', '^', '$', '+', '?', '(', ')', '[', ']', '{', '}', '\\'];
+
+    let alternatives: Vec<String> = split_alternatives(pattern)
+        .into_iter()
+        .map(|alt| {
+            let alt = alt.trim();
+            if alt.contains(REGEX_SYNTAX) {
+                alt.to_string()
+            } else {
+                regex::escape(alt).replace("\\*", ".*")
+            }
+        })
+        .collect();
+    format!("^(?:{})$", alternatives.join("|"))
+}
+
+/// Split `pattern` at each `|` that is outside groups, character classes
+/// and escapes.
+fn split_alternatives(pattern: &str) -> Vec<&str> {
+    let mut alternatives = Vec::new();
+    let (mut depth, mut in_class, mut escaped) = (0usize, false, false);
+    let mut start = 0;
+    for (i, c) in pattern.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ']' if in_class => in_class = false,
+            _ if in_class => {}
+            '[' => in_class = true,
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            '|' if depth == 0 => {
+                alternatives.push(&pattern[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&pattern[start..]);
+    alternatives
+}
+
+/// A [`HookMatcher`] with its patterns compiled.
+#[derive(Debug, Clone)]
+pub(crate) struct CompiledMatcher {
+    tool_name: Option<Regex>,
+    input: Vec<CompiledInputMatcher>,
+}
+
+#[derive(Debug, Clone)]
+enum CompiledInputMatcher {
+    Prefix { field: String, prefix: String },
+    Regex { field: String, regex: Regex },
+}
+
+impl CompiledMatcher {
+    /// Whether a use of `tool_name` with `tool_input` matches.
+    pub(crate) fn matches(&self, tool_name: &str, tool_input: &Value) -> bool {
+        if let Some(ref re) = self.tool_name {
+            if !re.is_match(tool_name) {
+                return false;
+            }
+        }
+
+        let field = |name: &str| tool_input.get(name).and_then(Value::as_str);
+        self.input.iter().all(|m| match m {
+            CompiledInputMatcher::Prefix { field: name, prefix } => {
+                field(name).is_some_and(|v| v.trim_start().starts_with(prefix.as_str()))
+            }
+            CompiledInputMatcher::Regex { field: name, regex } => {
+                field(name).is_some_and(|v| regex.is_match(v))
+            }
+        })
+    }
+}
+
+/// Fields the CLI sends with every hook input.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BaseHookInput {
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub transcript_path: Option<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub hook_event_name: Option<String>,
+    /// Set for tool-related events.
+    #[serde(default)]
+    pub tool_use_id: Option<String>,
 }
 
 /// Input for a preToolUse hook.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PreToolUseInput {
+    #[serde(flatten)]
+    pub base: BaseHookInput,
     #[serde(default)]
     pub tool_name: String,
     #[serde(default)]
     pub tool_input: Value,
 }
 
+impl PreToolUseInput {
+    /// The tool input, typed for built-in tools.
+    pub fn typed_input(&self) -> BuiltinToolInput {
+        parse_tool_input(&self.tool_name, &self.tool_input)
+    }
+}
+
 /// Input for a postToolUse hook.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PostToolUseInput {
+    #[serde(flatten)]
+    pub base: BaseHookInput,
     #[serde(default)]
     pub tool_name: String,
     #[serde(default)]
     pub tool_input: Value,
-    #[serde(default)]
+    /// The tool's result (`tool_response` in the CLI's payload).
+    #[serde(default, alias = "tool_response")]
     pub tool_output: Value,
 }
 
+impl PostToolUseInput {
+    /// The tool input, typed for built-in tools.
+    pub fn typed_input(&self) -> BuiltinToolInput {
+        parse_tool_input(&self.tool_name, &self.tool_input)
+    }
+}
+
 /// Input for a notification hook.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct NotificationInput {
+    #[serde(flatten)]
+    pub base: BaseHookInput,
     #[serde(default)]
     pub title: String,
     #[serde(default)]
     pub message: Option<String>,
 }
 
-/// Input for a stop hook.
+/// Input for a stop or subagentStop hook.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct StopInput {
+    #[serde(flatten)]
+    pub base: BaseHookInput,
     #[serde(default)]
     pub reason: Option<String>,
+    /// True if Claude is already continuing because of a stop hook.
+    #[serde(default)]
+    pub stop_hook_active: bool,
+}
+
+/// Input for a userPromptSubmit hook.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserPromptSubmitInput {
+    #[serde(flatten)]
+    pub base: BaseHookInput,
+    #[serde(default)]
+    pub prompt: String,
+}
+
+/// Input for a preCompact hook.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreCompactInput {
+    #[serde(flatten)]
+    pub base: BaseHookInput,
+    /// `"manual"` or `"auto"`.
+    #[serde(default)]
+    pub trigger: String,
+    #[serde(default)]
+    pub custom_instructions: Option<String>,
+}
+
+/// Input for a sessionStart hook.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionStartInput {
+    #[serde(flatten)]
+    pub base: BaseHookInput,
+    /// `"startup"`, `"resume"`, `"clear"` or `"compact"`.
+    #[serde(default)]
+    pub source: String,
+}
+
+/// Input for a sessionEnd hook.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionEndInput {
+    #[serde(flatten)]
+    pub base: BaseHookInput,
+    #[serde(default)]
+    pub reason: String,
 }
 
 /// Discriminated hook input passed to callbacks.
@@ -90,9 +357,50 @@ pub enum HookInput {
     PostToolUse(PostToolUseInput),
     Notification(NotificationInput),
     Stop(StopInput),
+    SubagentStop(StopInput),
+    UserPromptSubmit(UserPromptSubmitInput),
+    PreCompact(PreCompactInput),
+    SessionStart(SessionStartInput),
+    SessionEnd(SessionEndInput),
+}
+
+impl HookInput {
+    /// The event this input belongs to.
+    pub fn event(&self) -> HookEvent {
+        match self {
+            Self::PreToolUse(_) => HookEvent::PreToolUse,
+            Self::PostToolUse(_) => HookEvent::PostToolUse,
+            Self::Notification(_) => HookEvent::Notification,
+            Self::Stop(_) => HookEvent::Stop,
+            Self::SubagentStop(_) => HookEvent::SubagentStop,
+            Self::UserPromptSubmit(_) => HookEvent::UserPromptSubmit,
+            Self::PreCompact(_) => HookEvent::PreCompact,
+            Self::SessionStart(_) => HookEvent::SessionStart,
+            Self::SessionEnd(_) => HookEvent::SessionEnd,
+        }
+    }
+
+    /// The fields common to every hook input.
+    pub fn base(&self) -> &BaseHookInput {
+        match self {
+            Self::PreToolUse(i) => &i.base,
+            Self::PostToolUse(i) => &i.base,
+            Self::Notification(i) => &i.base,
+            Self::Stop(i) | Self::SubagentStop(i) => &i.base,
+            Self::UserPromptSubmit(i) => &i.base,
+            Self::PreCompact(i) => &i.base,
+            Self::SessionStart(i) => &i.base,
+            Self::SessionEnd(i) => &i.base,
+        }
+    }
 }
 
 /// Output from a hook callback.
+///
+/// Which fields take effect depends on the event: `updated_input` applies to
+/// PreToolUse, `additional_context` to PostToolUse, UserPromptSubmit and
+/// SessionStart. A `Block` decision on Stop/SubagentStop makes Claude keep
+/// going, with `reason` as the instruction.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct HookOutput {
     /// If set, blocks the tool use with this reason.
@@ -101,6 +409,24 @@ pub struct HookOutput {
     /// Optional reason/message.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
+    /// Replacement tool input (PreToolUse).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_input: Option<Value>,
+    /// Extra context added to the conversation for Claude.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub additional_context: Option<String>,
+    /// Message shown to the user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_message: Option<String>,
+    /// Hide the hook's output from the transcript.
+    #[serde(default)]
+    pub suppress_output: bool,
+    /// `Some(false)` stops Claude entirely after this hook.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub should_continue: Option<bool>,
+    /// Reason shown to the user when `should_continue` is false.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -126,6 +452,16 @@ impl HookDecision {
             Self::Ignore => "ignore",
         }
     }
+
+    /// The decision as a PreToolUse `permissionDecision`: `allow` or `deny`.
+    /// `None` for `Ignore`, which leaves it to the normal permission flow.
+    pub fn permission_decision(&self) -> Option<&'static str> {
+        match self {
+            Self::Approve => Some("allow"),
+            Self::Block => Some("deny"),
+            Self::Ignore => None,
+        }
+    }
 }
 
 impl HookOutput {
@@ -133,7 +469,7 @@ impl HookOutput {
     pub fn approve() -> Self {
         Self {
             decision: Some(HookDecision::Approve),
-            reason: None,
+            ..Default::default()
         }
     }
 
@@ -142,6 +478,7 @@ impl HookOutput {
         Self {
             decision: Some(HookDecision::Block),
             reason: Some(reason.into()),
+            ..Default::default()
         }
     }
 
@@ -149,17 +486,119 @@ impl HookOutput {
     pub fn ignore() -> Self {
         Self {
             decision: Some(HookDecision::Ignore),
-            reason: None,
+            ..Default::default()
         }
     }
+
+    /// Approve a tool use with rewritten input (PreToolUse).
+    #[must_use]
+    pub fn modify_input(input: Value) -> Self {
+        Self::approve().with_updated_input(input)
+    }
+
+    /// Keep Claude working instead of stopping (Stop/SubagentStop).
+    #[must_use]
+    pub fn force_continue(reason: impl Into<String>) -> Self {
+        Self::block(reason)
+    }
+
+    /// Stop Claude entirely, showing `reason` to the user.
+    #[must_use]
+    pub fn stop(reason: impl Into<String>) -> Self {
+        Self {
+            should_continue: Some(false),
+            stop_reason: Some(reason.into()),
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn with_updated_input(mut self, input: Value) -> Self {
+        self.updated_input = Some(input);
+        self
+    }
+
+    #[must_use]
+    pub fn with_additional_context(mut self, context: impl Into<String>) -> Self {
+        self.additional_context = Some(context.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_system_message(mut self, message: impl Into<String>) -> Self {
+        self.system_message = Some(message.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_suppress_output(mut self) -> Self {
+        self.suppress_output = true;
+        self
+    }
 }
 
+/// Time a hook may run before it counts as failed, unless overridden.
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// A registered hook definition.
+///
+/// When several hooks match the same event they run one after another in
+/// registration order:
+///
+/// - the first `Block` wins and later hooks are skipped; otherwise any
+///   `Approve` wins over no decision;
+/// - `updated_input` chains: each PreToolUse hook sees the tool input as
+///   modified by the hooks before it;
+/// - a stop (`should_continue: Some(false)`) ends the chain;
+/// - additional context and system messages are joined with newlines.
 #[derive(Clone)]
 pub struct HookDefinition {
     pub event: HookEvent,
     pub matcher: HookMatcher,
     pub callback: HookCallback,
+    /// Maximum run time; `None` uses [`DEFAULT_HOOK_TIMEOUT`].
+    pub timeout: Option<Duration>,
+    /// What to do when the hook times out, panics or gets malformed input.
+    pub on_failure: HookFailurePolicy,
+}
+
+impl HookDefinition {
+    #[must_use]
+    pub fn new(event: HookEvent, matcher: HookMatcher, callback: HookCallback) -> Self {
+        Self {
+            event,
+            matcher,
+            callback,
+            timeout: None,
+            on_failure: HookFailurePolicy::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Block the action if this hook fails, instead of ignoring it.
+    #[must_use]
+    pub fn fail_closed(mut self) -> Self {
+        self.on_failure = HookFailurePolicy::FailClosed;
+        self
+    }
+}
+
+/// How a failed hook affects the action it guards.
+///
+/// Either way the failure is reported as a non-fatal [`Error::Hook`] on the
+/// message stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HookFailurePolicy {
+    /// Carry on as if the hook had not run.
+    #[default]
+    FailOpen,
+    /// Block, as if the hook returned [`HookOutput::block`].
+    FailClosed,
 }
 
 impl std::fmt::Debug for HookDefinition {
@@ -168,6 +607,8 @@ impl std::fmt::Debug for HookDefinition {
             .field("event", &self.event)
             .field("matcher", &self.matcher)
             .field("callback", &"<fn>")
+            .field("timeout", &self.timeout)
+            .field("on_failure", &self.on_failure)
             .finish()
     }
 }
@@ -184,3 +625,66 @@ where
 {
     Arc::new(move |input| Box::pin(f(input)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(matcher: &HookMatcher, tool: &str, input: Value) -> bool {
+        matcher.compile().unwrap().matches(tool, &input)
+    }
+
+    #[test]
+    fn matcher_alternation_and_exact_names() {
+        let m = HookMatcher::tool("Edit|Write|MultiEdit");
+        assert!(matches(&m, "Edit", Value::Null));
+        assert!(matches(&m, "MultiEdit", Value::Null));
+        assert!(!matches(&m, "NotebookEdit", Value::Null));
+        assert!(!matches(&m, "Read", Value::Null));
+    }
+
+    #[test]
+    fn matcher_globs_and_regexes() {
+        let glob = HookMatcher::tool("mcp__github__*");
+        assert!(matches(&glob, "mcp__github__create_issue", Value::Null));
+        assert!(!matches(&glob, "mcp__gitlab__create_issue", Value::Null));
+
+        let re = HookMatcher::tool("mcp__.*__search");
+        assert!(matches(&re, "mcp__docs__search", Value::Null));
+        assert!(!matches(&re, "mcp__docs__fetch", Value::Null));
+
+        assert!(matches(&HookMatcher::default(), "Anything", Value::Null));
+    }
+
+    #[test]
+    fn matcher_alternation_inside_groups() {
+        let m = HookMatcher::tool("mcp__(docs|wiki)__search|Read");
+        assert!(matches(&m, "mcp__docs__search", Value::Null));
+        assert!(matches(&m, "mcp__wiki__search", Value::Null));
+        assert!(matches(&m, "Read", Value::Null));
+        assert!(!matches(&m, "mcp__docs__fetch", Value::Null));
+        assert!(!matches(&m, "docs", Value::Null));
+
+        let m = HookMatcher::tool(r"[|a]x|Edit");
+        assert!(matches(&m, "|x", Value::Null));
+        assert!(matches(&m, "Edit", Value::Null));
+    }
+
+    #[test]
+    fn matcher_on_tool_input() {
+        let m = HookMatcher::tool("Bash").with_input_prefix("command", "git push");
+        assert!(matches(&m, "Bash", serde_json::json!({"command": "  git push origin"})));
+        assert!(!matches(&m, "Bash", serde_json::json!({"command": "git status"})));
+        assert!(!matches(&m, "Bash", serde_json::json!({})));
+
+        let m = HookMatcher::default().with_input_regex("file_path", r"\.rs$");
+        assert!(matches(&m, "Write", serde_json::json!({"file_path": "src/lib.rs"})));
+        assert!(!matches(&m, "Write", serde_json::json!({"file_path": "README.md"})));
+    }
+
+    #[test]
+    fn matcher_invalid_regex_is_an_error() {
+        let m = HookMatcher::tool("Bash").with_input_regex("command", "(");
+        assert!(matches!(m.compile(), Err(Error::Hook(_))));
+    }
+}
@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+/// Resource limits applied to the CLI subprocess.
+///
+/// The rlimit fields are applied with `setrlimit` in the child before `exec`
+/// (unix only) and are inherited by everything the CLI spawns. Unset fields
+/// leave the inherited limit untouched.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// Maximum virtual address space in bytes (`RLIMIT_AS`).
+    pub address_space_bytes: Option<u64>,
+
+    /// Maximum CPU time in seconds (`RLIMIT_CPU`).
+    pub cpu_seconds: Option<u64>,
+
+    /// Maximum number of open file descriptors (`RLIMIT_NOFILE`).
+    pub open_files: Option<u64>,
+
+    /// Maximum number of processes (`RLIMIT_NPROC`).
+    ///
+    /// Note that the kernel counts this per user, not per process tree.
+    pub max_processes: Option<u64>,
+
+    /// Wall-clock limit for the whole run. When it elapses the transport is
+    /// torn down and consumers receive [`Error::WallClockLimitExceeded`](crate::Error::WallClockLimitExceeded).
+    pub wall_clock: Option<Duration>,
+}
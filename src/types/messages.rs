@@ -3,13 +3,17 @@ use serde_json::Value;
 use tokio_stream::{Stream, StreamExt};
 
 use super::content::ContentBlock;
+use super::sandbox::EgressEvent;
 use crate::error::Result;
 
 /// A message from the Claude CLI streaming protocol.
 ///
 /// The CLI emits newline-delimited JSON objects with a top-level `type` field.
 /// Each variant corresponds to one of these message types.
-#[derive(Debug, Clone)]
+///
+/// Serializes back to the same stream-json shape (fields that were `null`
+/// on the wire are omitted) and deserializes like the stream is parsed.
+#[derive(Debug, Clone, PartialEq)]
 #[non_exhaustive]
 pub enum Message {
     /// System-level message (init acknowledgment, etc.)
@@ -41,36 +45,58 @@ pub enum Message {
 }
 
 /// An assistant response with content blocks.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AssistantMessage {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
     #[serde(default)]
     pub content: Vec<ContentBlock>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub stop_reason: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub usage: Option<Usage>,
+    /// Tool use (e.g. a `Task` subagent) this message belongs to, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_tool_use_id: Option<String>,
+    /// Session the message belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Transcript id of the message; for user messages, what `rewind_files` takes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
     /// Raw extra fields we don't explicitly model.
     #[serde(flatten)]
     pub extra: Value,
 }
 
 /// A user message as echoed back by the CLI.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserMessage {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
     #[serde(default)]
     pub content: UserContent,
+    /// Tool use (e.g. a `Task` subagent) this message belongs to, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_tool_use_id: Option<String>,
+    /// Session the message belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Transcript id of the message; for user messages, what `rewind_files` takes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
     #[serde(flatten)]
     pub extra: Value,
 }
 
 /// User message content can be a string or structured blocks.
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+///
+/// Also used as input when sending a message: build `Blocks` from
+/// [`ContentBlock::text`], [`ContentBlock::image_file`] and
+/// [`ContentBlock::document_file`] to attach images or PDFs to a prompt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 #[serde(untagged)]
 pub enum UserContent {
     Text(String),
@@ -79,48 +105,142 @@ pub enum UserContent {
     Empty,
 }
 
+impl From<&str> for UserContent {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<String> for UserContent {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<Vec<ContentBlock>> for UserContent {
+    fn from(blocks: Vec<ContentBlock>) -> Self {
+        Self::Blocks(blocks)
+    }
+}
+
+impl From<ContentBlock> for UserContent {
+    fn from(block: ContentBlock) -> Self {
+        Self::Blocks(vec![block])
+    }
+}
+
+/// A user message to send to the CLI, as yielded by an input stream.
+#[derive(Debug, Clone, Default)]
+pub struct UserInput {
+    /// Message content (text, images, documents).
+    pub content: UserContent,
+    /// Session to send the message to.
+    pub session_id: Option<String>,
+    /// Tool use this message responds to, if any.
+    pub parent_tool_use_id: Option<String>,
+}
+
+impl UserInput {
+    #[must_use]
+    pub fn new(content: impl Into<UserContent>) -> Self {
+        Self {
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_parent_tool_use_id(mut self, parent_tool_use_id: impl Into<String>) -> Self {
+        self.parent_tool_use_id = Some(parent_tool_use_id.into());
+        self
+    }
+}
+
+impl From<&str> for UserInput {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<String> for UserInput {
+    fn from(text: String) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<UserContent> for UserInput {
+    fn from(content: UserContent) -> Self {
+        Self::new(content)
+    }
+}
+
 /// Result message indicating the end of a query turn.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ResultMessage {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub subtype: Option<String>,
     #[serde(default)]
     pub is_error: bool,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub duration_ms: Option<f64>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub duration_api_ms: Option<f64>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub num_turns: Option<u32>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub session_id: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub cost_usd: Option<f64>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub total_cost_usd: Option<f64>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub usage: Option<Usage>,
     #[serde(flatten)]
     pub extra: Value,
 }
 
 /// Token usage information.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Usage {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub input_tokens: Option<u64>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub output_tokens: Option<u64>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub cache_creation_input_tokens: Option<u64>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub cache_read_input_tokens: Option<u64>,
     #[serde(flatten)]
     pub extra: Value,
 }
 
+impl Serialize for Message {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        crate::message_parser::message_to_value(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let raw = Value::deserialize(deserializer)?;
+        crate::message_parser::parse_message(raw).map_err(serde::de::Error::custom)
+    }
+}
+
 impl Message {
     /// Returns true if this is a Result message (end of turn).
     pub fn is_result(&self) -> bool {
@@ -148,6 +268,16 @@ impl Message {
         }
     }
 
+    /// Decode an egress proxy event (a System message with subtype `"egress"`).
+    pub fn egress_event(&self) -> Option<EgressEvent> {
+        match self {
+            Message::System { subtype, data } if subtype == "egress" => {
+                serde_json::from_value(data.clone()).ok()
+            }
+            _ => None,
+        }
+    }
+
     /// Get the session ID from a Result message.
     pub fn session_id(&self) -> Option<&str> {
         match self {
@@ -170,3 +300,106 @@ pub(crate) async fn collect_until_result(stream: &mut (impl Stream<Item = Result
     }
     Ok(messages)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Wire lines as the CLI writes them, minus `null` fields.
+    fn wire_lines() -> Vec<Value> {
+        vec![
+            json!({
+                "type": "assistant",
+                "parent_tool_use_id": null,
+                "session_id": "sess_1",
+                "uuid": "u2",
+                "message": {
+                    "id": "msg_1",
+                    "type": "message",
+                    "role": "assistant",
+                    "model": "claude-sonnet-4-5",
+                    "content": [
+                        {"type": "thinking", "thinking": "Hmm", "signature": "sig"},
+                        {"type": "text", "text": "Listing."},
+                        {"type": "tool_use", "id": "toolu_1", "name": "Bash", "input": {"command": "ls"}}
+                    ],
+                    "stop_reason": "tool_use",
+                    "usage": {"input_tokens": 10, "output_tokens": 5, "service_tier": "standard"}
+                }
+            }),
+            json!({
+                "type": "user",
+                "parent_tool_use_id": "toolu_task",
+                "session_id": "sess_1",
+                "uuid": "u3",
+                "message": {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1", "content": "a.txt", "is_error": false}
+                ]}
+            }),
+            json!({
+                "type": "user",
+                "parent_tool_use_id": null,
+                "message": {"role": "user", "content": "Thanks"}
+            }),
+            json!({
+                "type": "result",
+                "subtype": "success",
+                "is_error": false,
+                "duration_ms": 1234.0,
+                "num_turns": 2,
+                "session_id": "sess_1",
+                "total_cost_usd": 0.05,
+                "usage": {"input_tokens": 100, "output_tokens": 20},
+                "result": "Done",
+                "uuid": "r1"
+            }),
+            json!({
+                "type": "system",
+                "subtype": "init",
+                "session_id": "sess_1",
+                "tools": ["Bash", "Read"],
+                "model": "claude-sonnet-4-5"
+            }),
+            json!({"type": "stream_event", "uuid": "e1", "event": {"type": "content_block_delta"}}),
+        ]
+    }
+
+    #[test]
+    fn serializes_to_the_wire_format() {
+        for line in wire_lines() {
+            let message: Message = serde_json::from_value(line.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&message).unwrap(), line);
+        }
+    }
+
+    #[test]
+    fn round_trips_every_variant() {
+        let messages: Vec<Message> = wire_lines()
+            .into_iter()
+            .map(|line| crate::message_parser::parse_message(line).unwrap())
+            .collect();
+        assert!(matches!(messages[0], Message::Assistant { .. }));
+        assert!(matches!(messages[1], Message::User { .. }));
+        assert!(matches!(messages[3], Message::Result { .. }));
+        assert!(matches!(messages[4], Message::System { .. }));
+        assert!(matches!(messages[5], Message::Unknown { .. }));
+
+        let json = serde_json::to_string(&messages).unwrap();
+        let back: Vec<Message> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, messages);
+
+        match &back[1] {
+            Message::User { message } => {
+                assert_eq!(message.uuid.as_deref(), Some("u3"));
+                assert_eq!(message.parent_tool_use_id.as_deref(), Some("toolu_task"));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn deserializing_a_bad_message_fails() {
+        assert!(serde_json::from_value::<Message>(json!({"no_type": true})).is_err());
+    }
+}
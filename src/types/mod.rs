@@ -2,21 +2,29 @@ pub mod agents;
 pub mod content;
 pub mod control;
 pub mod hooks;
+pub mod limits;
 pub mod mcp_config;
 pub mod messages;
 pub mod options;
 pub mod permissions;
 pub mod sandbox;
+pub mod tools;
 
 // Re-exports for convenience.
 pub use agents::AgentDefinition;
 pub use content::ContentBlock;
 pub use control::{SDKCapabilities, SDKControlCommand};
 pub use hooks::{
-    HookCallback, HookDecision, HookDefinition, HookEvent, HookInput, HookMatcher, HookOutput,
+    HookCallback, HookDecision, HookDefinition, HookEvent, HookFailurePolicy, HookInput,
+    HookMatcher, HookOutput, InputMatcher, DEFAULT_HOOK_TIMEOUT,
 };
+pub use limits::ResourceLimits;
 pub use mcp_config::{McpServerConfig, McpServerEntry, McpServerStatus};
-pub use messages::{AssistantMessage, Message, ResultMessage, Usage, UserMessage};
+pub use messages::{AssistantMessage, Message, ResultMessage, Usage, UserContent, UserInput, UserMessage};
 pub use options::ClaudeAgentOptions;
-pub use permissions::{CanUseToolCallback, CanUseToolInput, PermissionMode, PermissionResult};
-pub use sandbox::{SandboxSettings, SandboxType};
+pub use permissions::{
+    CanUseToolCallback, CanUseToolInput, PermissionBehavior, PermissionMode, PermissionResult,
+    PermissionRule, PermissionUpdate, PermissionUpdateDestination,
+};
+pub use sandbox::{EgressEvent, EgressPolicy, SandboxSettings, SandboxType};
+pub use tools::{parse_tool_input, BuiltinToolInput};
@@ -3,16 +3,18 @@ use std::path::PathBuf;
 
 use super::agents::AgentDefinition;
 use super::hooks::HookDefinition;
+use super::limits::ResourceLimits;
 use super::mcp_config::McpServerConfig;
 use super::permissions::{CanUseToolCallback, PermissionMode};
 use super::sandbox::SandboxSettings;
+use crate::permissions::audit::AuditLog;
 use crate::transport::cli_discovery;
 
 /// Configuration options for a Claude Agent SDK query or client.
 ///
 /// All fields are public with sensible defaults. Use `..Default::default()` for
 /// fields you don't need to set.
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct ClaudeAgentOptions {
     // --- Core ---
     /// The prompt/message to send. Can be set here or passed to query().
@@ -34,12 +36,19 @@ pub struct ClaudeAgentOptions {
     pub max_tokens: Option<u32>,
 
     // --- Session ---
-    /// Resume an existing session by ID.
+    /// Use this ID for the session (`--session-id`).
     pub session_id: Option<String>,
 
+    /// Resume an existing session by ID (`--resume`).
+    pub resume: Option<String>,
+
     /// Continue the most recent session.
     pub continue_session: bool,
 
+    /// With `resume` or `continue_session`, branch into a new session
+    /// instead of appending to the resumed one.
+    pub fork_session: bool,
+
     // --- Working directory ---
     /// Working directory for the CLI process.
     pub cwd: Option<PathBuf>,
@@ -48,12 +57,18 @@ pub struct ClaudeAgentOptions {
     /// Permission mode for tool usage.
     pub permission_mode: PermissionMode,
 
-    /// Specific tools to allow (when using AllowedTools mode).
+    /// Tools or rules (e.g. `Bash(git status:*)`) to allow without asking.
     pub allowed_tools: Vec<String>,
 
+    /// Tools or rules to deny outright; these win over `allowed_tools`.
+    pub disallowed_tools: Vec<String>,
+
     /// Custom permission callback.
     pub can_use_tool: Option<CanUseToolCallback>,
 
+    /// Record every permission decision (`can_use_tool` and PreToolUse hooks).
+    pub audit_log: Option<AuditLog>,
+
     // --- Hooks ---
     /// Registered hook definitions.
     pub hooks: Vec<HookDefinition>,
@@ -70,6 +85,9 @@ pub struct ClaudeAgentOptions {
     /// Sandbox configuration.
     pub sandbox: Option<SandboxSettings>,
 
+    /// Resource limits for the CLI process.
+    pub resource_limits: Option<ResourceLimits>,
+
     // --- CLI flags ---
     /// Additional environment variables for the CLI process.
     pub env: HashMap<String, String>,
@@ -125,6 +143,7 @@ impl std::fmt::Debug for ClaudeAgentOptions {
             }))
             .field("max_turns", &self.max_turns)
             .field("session_id", &self.session_id)
+            .field("resume", &self.resume)
             .field("permission_mode", &self.permission_mode)
             .field("verbose", &self.verbose)
             .field("hooks_count", &self.hooks.len())
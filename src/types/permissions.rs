@@ -4,35 +4,61 @@ use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 
-/// Permission mode for tool usage.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase")]
+use crate::types::tools::{parse_tool_input, BuiltinToolInput};
+
+/// The CLI's permission mode (`--permission-mode`).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
 #[non_exhaustive]
 pub enum PermissionMode {
-    /// Default permissions - prompt user for dangerous tools.
+    /// Ask (via `can_use_tool`) for anything not covered by allow rules.
+    #[default]
     Default,
-    /// Accept all tool uses without prompting.
-    AcceptAll,
-    /// Deny all tool uses.
-    DenyAll,
-    /// Use allowed-tools list from CLI config.
-    AllowedTools,
+    /// Accept file edits without asking.
+    AcceptEdits,
+    /// Plan only: read-only tools, no edits or commands.
+    Plan,
+    /// Run every tool without asking.
+    BypassPermissions,
+}
+
+impl PermissionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::AcceptEdits => "acceptEdits",
+            Self::Plan => "plan",
+            Self::BypassPermissions => "bypassPermissions",
+        }
+    }
 }
 
-impl Default for PermissionMode {
-    fn default() -> Self {
-        Self::Default
+impl std::fmt::Display for PermissionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
 /// Result from a permission check callback.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PermissionResult {
     /// Whether the tool use is allowed.
     pub allowed: bool,
     /// Optional reason for denial.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
+    /// Replacement tool input to run with, when allowed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_input: Option<Value>,
+    /// Stop the current turn as well, when denied.
+    #[serde(default)]
+    pub interrupt: bool,
+    /// Permission rule or mode changes to apply, when allowed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub updated_permissions: Vec<PermissionUpdate>,
+    /// What made the decision, for the audit log (not sent to the CLI).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_by: Option<String>,
 }
 
 impl PermissionResult {
@@ -40,7 +66,7 @@ impl PermissionResult {
     pub fn allow() -> Self {
         Self {
             allowed: true,
-            reason: None,
+            ..Default::default()
         }
     }
 
@@ -49,8 +75,145 @@ impl PermissionResult {
         Self {
             allowed: false,
             reason: Some(reason.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Deny and interrupt the current turn.
+    #[must_use]
+    pub fn deny_and_interrupt(reason: impl Into<String>) -> Self {
+        Self {
+            interrupt: true,
+            ..Self::deny(reason)
         }
     }
+
+    #[must_use]
+    pub fn with_updated_input(mut self, input: Value) -> Self {
+        self.updated_input = Some(input);
+        self
+    }
+
+    #[must_use]
+    pub fn with_permission_update(mut self, update: PermissionUpdate) -> Self {
+        self.updated_permissions.push(update);
+        self
+    }
+
+    #[must_use]
+    pub fn decided_by(mut self, decided_by: impl Into<String>) -> Self {
+        self.decided_by = Some(decided_by.into());
+        self
+    }
+}
+
+/// A change to the CLI's permission rules or mode.
+///
+/// Returned from a permission callback (e.g. for "always allow this") and
+/// offered by the CLI as [`CanUseToolInput::permission_suggestions`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PermissionUpdate {
+    AddRules {
+        rules: Vec<PermissionRule>,
+        behavior: PermissionBehavior,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionUpdateDestination>,
+    },
+    ReplaceRules {
+        rules: Vec<PermissionRule>,
+        behavior: PermissionBehavior,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionUpdateDestination>,
+    },
+    RemoveRules {
+        rules: Vec<PermissionRule>,
+        behavior: PermissionBehavior,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionUpdateDestination>,
+    },
+    SetMode {
+        mode: PermissionMode,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionUpdateDestination>,
+    },
+    AddDirectories {
+        directories: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionUpdateDestination>,
+    },
+    RemoveDirectories {
+        directories: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionUpdateDestination>,
+    },
+}
+
+impl PermissionUpdate {
+    /// Allow `rule` for the rest of the session.
+    #[must_use]
+    pub fn allow_for_session(rule: PermissionRule) -> Self {
+        Self::AddRules {
+            rules: vec![rule],
+            behavior: PermissionBehavior::Allow,
+            destination: Some(PermissionUpdateDestination::Session),
+        }
+    }
+
+    /// Switch the permission mode for the rest of the session.
+    #[must_use]
+    pub fn set_mode(mode: PermissionMode) -> Self {
+        Self::SetMode {
+            mode,
+            destination: Some(PermissionUpdateDestination::Session),
+        }
+    }
+}
+
+/// A permission rule: a tool, optionally narrowed by content
+/// (e.g. `Bash` with `git status:*`).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionRule {
+    pub tool_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_content: Option<String>,
+}
+
+impl PermissionRule {
+    /// A rule covering every use of `tool_name`.
+    #[must_use]
+    pub fn tool(tool_name: impl Into<String>) -> Self {
+        Self {
+            tool_name: tool_name.into(),
+            rule_content: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_content(mut self, content: impl Into<String>) -> Self {
+        self.rule_content = Some(content.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionBehavior {
+    Allow,
+    Deny,
+    Ask,
+}
+
+/// Where a permission update is stored.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionUpdateDestination {
+    UserSettings,
+    ProjectSettings,
+    LocalSettings,
+    /// In memory, for the current session only.
+    Session,
 }
 
 /// Input provided to the can_use_tool callback.
@@ -58,6 +221,19 @@ impl PermissionResult {
 pub struct CanUseToolInput {
     pub tool_name: String,
     pub input: Value,
+    /// Id of the tool use being checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_use_id: Option<String>,
+    /// Updates the CLI suggests for an "always allow" answer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permission_suggestions: Vec<PermissionUpdate>,
+}
+
+impl CanUseToolInput {
+    /// The tool input, typed for built-in tools.
+    pub fn typed_input(&self) -> BuiltinToolInput {
+        parse_tool_input(&self.tool_name, &self.input)
+    }
 }
 
 /// Async callback for permission checks.
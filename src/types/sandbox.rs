@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
 /// Sandbox configuration for the CLI process.
@@ -10,6 +12,96 @@ pub struct SandboxSettings {
     /// Network access allowed.
     #[serde(default)]
     pub allow_network: bool,
+
+    /// Extra paths the CLI may read (in addition to `cwd` and system paths).
+    ///
+    /// Used by [`SandboxType::Namespaces`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub read_only_paths: Vec<PathBuf>,
+
+    /// Extra paths the CLI may read and write (in addition to `cwd`).
+    ///
+    /// Used by [`SandboxType::Namespaces`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub writable_paths: Vec<PathBuf>,
+
+    /// Let the CLI read and write the temp dirs (`/tmp`, `/var/tmp` and
+    /// `$TMPDIR`), which Bash and many other tools rely on.
+    ///
+    /// Used by [`SandboxType::Namespaces`]. Defaults to true.
+    #[serde(default = "default_true")]
+    pub writable_tmp: bool,
+
+    /// Let the CLI read and write its own state in the home directory: the
+    /// config dir (`$CLAUDE_CONFIG_DIR` or `~/.claude`) and `~/.claude.json`.
+    /// The rest of the home directory stays inaccessible.
+    ///
+    /// Used by [`SandboxType::Namespaces`]. Defaults to false.
+    #[serde(default)]
+    pub home_access: bool,
+
+    /// Route the CLI's HTTP(S) traffic through an in-process proxy that
+    /// enforces this host policy.
+    ///
+    /// The proxy is set via `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY` (with
+    /// any inherited `NO_PROXY` removed), so it only governs clients that
+    /// honor those variables. Only HTTPS `CONNECT` tunnels are let through;
+    /// plain HTTP requests are refused. It listens on the host loopback,
+    /// so combining it with `allow_network: false` under
+    /// [`SandboxType::Namespaces`] is rejected at connect time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub egress: Option<EgressPolicy>,
+}
+
+/// Host allowlist/denylist for the egress proxy.
+///
+/// Patterns are exact host names (`api.anthropic.com`), subdomain wildcards
+/// (`*.github.com`, which does not match `github.com` itself) or `*`.
+/// Matching is case-insensitive. The deny list wins; an empty allow list
+/// allows every host that isn't denied.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EgressPolicy {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+impl EgressPolicy {
+    /// Whether a connection to `host` is permitted.
+    pub fn is_allowed(&self, host: &str) -> bool {
+        if self.deny.iter().any(|p| host_matches(p, host)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|p| host_matches(p, host))
+    }
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let host = host.trim_end_matches('.');
+    if pattern == "*" {
+        return true;
+    }
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => {
+            let (host, suffix) = (host.as_bytes(), suffix.as_bytes());
+            host.len() > suffix.len()
+                && host[host.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+                && host[host.len() - suffix.len() - 1] == b'.'
+        }
+        None => host.eq_ignore_ascii_case(pattern),
+    }
+}
+
+/// A connection attempt seen by the egress proxy.
+///
+/// Delivered on the message stream as a `Message::System` with subtype
+/// `"egress"`; see [`Message::egress_event`](crate::Message::egress_event).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EgressEvent {
+    pub host: String,
+    pub port: u16,
+    pub allowed: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -18,6 +110,10 @@ pub enum SandboxType {
     None,
     Docker,
     Firecracker,
+    /// Linux only: Landlock confines the filesystem to `cwd`, system paths
+    /// and the configured extra paths; when `allow_network` is false the CLI
+    /// also runs in its own (empty) user and network namespaces.
+    Namespaces,
 }
 
 impl Default for SandboxSettings {
@@ -25,6 +121,42 @@ impl Default for SandboxSettings {
         Self {
             sandbox_type: None,
             allow_network: true,
+            read_only_paths: Vec::new(),
+            writable_paths: Vec::new(),
+            writable_tmp: true,
+            home_access: false,
+            egress: None,
         }
     }
 }
+
+fn default_true() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn egress_policy_matching() {
+        let policy = EgressPolicy {
+            allow: vec!["api.anthropic.com".into(), "*.github.com".into()],
+            deny: vec!["gist.github.com".into()],
+        };
+        assert!(policy.is_allowed("api.anthropic.com"));
+        assert!(policy.is_allowed("API.Anthropic.com."));
+        assert!(policy.is_allowed("raw.github.com"));
+        assert!(!policy.is_allowed("github.com"));
+        assert!(!policy.is_allowed("evilgithub.com"));
+        assert!(!policy.is_allowed("gist.github.com"));
+        assert!(!policy.is_allowed("example.com"));
+
+        let deny_all = EgressPolicy {
+            allow: vec![],
+            deny: vec!["*".into()],
+        };
+        assert!(!deny_all.is_allowed("example.com"));
+        assert!(EgressPolicy::default().is_allowed("example.com"));
+    }
+}
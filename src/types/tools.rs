@@ -0,0 +1,283 @@
+//! Typed inputs for the CLI's built-in tools.
+//!
+//! Tool inputs arrive as raw JSON (`ContentBlock::ToolUse::input`,
+//! `CanUseToolInput::input`, hook `tool_input`); [`parse_tool_input`] turns
+//! them into a [`BuiltinToolInput`]. Unknown tools, and inputs that don't fit
+//! the expected shape, fall back to [`BuiltinToolInput::Other`].
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Input of a built-in tool, by tool name.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum BuiltinToolInput {
+    Bash(BashInput),
+    Read(ReadInput),
+    Edit(EditInput),
+    MultiEdit(MultiEditInput),
+    Write(WriteInput),
+    NotebookEdit(NotebookEditInput),
+    Glob(GlobInput),
+    Grep(GrepInput),
+    WebFetch(WebFetchInput),
+    WebSearch(WebSearchInput),
+    Task(TaskInput),
+    TodoWrite(TodoWriteInput),
+    /// An MCP or unrecognized tool, or input that didn't parse.
+    Other(Value),
+}
+
+/// Parse the input of tool `name`.
+pub fn parse_tool_input(name: &str, input: &Value) -> BuiltinToolInput {
+    fn parse<T: serde::de::DeserializeOwned>(
+        input: &Value,
+        wrap: fn(T) -> BuiltinToolInput,
+    ) -> BuiltinToolInput {
+        serde_json::from_value(input.clone())
+            .map(wrap)
+            .unwrap_or_else(|_| BuiltinToolInput::Other(input.clone()))
+    }
+
+    match name {
+        "Bash" => parse(input, BuiltinToolInput::Bash),
+        "Read" => parse(input, BuiltinToolInput::Read),
+        "Edit" => parse(input, BuiltinToolInput::Edit),
+        "MultiEdit" => parse(input, BuiltinToolInput::MultiEdit),
+        "Write" => parse(input, BuiltinToolInput::Write),
+        "NotebookEdit" => parse(input, BuiltinToolInput::NotebookEdit),
+        "Glob" => parse(input, BuiltinToolInput::Glob),
+        "Grep" => parse(input, BuiltinToolInput::Grep),
+        "WebFetch" => parse(input, BuiltinToolInput::WebFetch),
+        "WebSearch" => parse(input, BuiltinToolInput::WebSearch),
+        "Task" => parse(input, BuiltinToolInput::Task),
+        "TodoWrite" => parse(input, BuiltinToolInput::TodoWrite),
+        _ => BuiltinToolInput::Other(input.clone()),
+    }
+}
+
+impl BuiltinToolInput {
+    /// The file or directory the tool operates on, if any.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::Read(i) => Some(&i.file_path),
+            Self::Edit(i) => Some(&i.file_path),
+            Self::MultiEdit(i) => Some(&i.file_path),
+            Self::Write(i) => Some(&i.file_path),
+            Self::NotebookEdit(i) => Some(&i.notebook_path),
+            Self::Glob(i) => i.path.as_deref(),
+            Self::Grep(i) => i.path.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BashInput {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Timeout in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    pub run_in_background: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReadInput {
+    pub file_path: String,
+    /// First line to read (1-based).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    /// Number of lines to read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EditInput {
+    pub file_path: String,
+    pub old_string: String,
+    pub new_string: String,
+    #[serde(default)]
+    pub replace_all: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MultiEditInput {
+    pub file_path: String,
+    pub edits: Vec<EditOperation>,
+}
+
+/// One edit of a [`MultiEditInput`], applied in order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EditOperation {
+    pub old_string: String,
+    pub new_string: String,
+    #[serde(default)]
+    pub replace_all: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct WriteInput {
+    pub file_path: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct NotebookEditInput {
+    pub notebook_path: String,
+    pub new_source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cell_id: Option<String>,
+    /// `code` or `markdown`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cell_type: Option<String>,
+    /// `replace`, `insert` or `delete`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit_mode: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GlobInput {
+    pub pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GrepInput {
+    pub pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// File glob filter, e.g. `*.rs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+    /// File type filter, e.g. `rust`.
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<String>,
+    /// `content`, `files_with_matches` or `count`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_mode: Option<String>,
+    #[serde(default, rename = "-i")]
+    pub case_insensitive: bool,
+    #[serde(default, rename = "-n")]
+    pub line_numbers: bool,
+    #[serde(default, rename = "-A", skip_serializing_if = "Option::is_none")]
+    pub after_context: Option<u32>,
+    #[serde(default, rename = "-B", skip_serializing_if = "Option::is_none")]
+    pub before_context: Option<u32>,
+    #[serde(default, rename = "-C", skip_serializing_if = "Option::is_none")]
+    pub context: Option<u32>,
+    #[serde(default)]
+    pub multiline: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head_limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct WebFetchInput {
+    pub url: String,
+    pub prompt: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct WebSearchInput {
+    pub query: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_domains: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocked_domains: Vec<String>,
+}
+
+/// Input of the `Task` tool, which starts a subagent.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TaskInput {
+    pub description: String,
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subagent_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TodoWriteInput {
+    pub todos: Vec<Todo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Todo {
+    pub content: String,
+    pub status: TodoStatus,
+    /// Present-tense form shown while in progress ("Running tests").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_form: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoStatus {
+    Pending,
+    InProgress,
+    Completed,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_builtin_tools() {
+        match parse_tool_input("Bash", &json!({"command": "ls", "timeout": 5000})) {
+            BuiltinToolInput::Bash(bash) => {
+                assert_eq!(bash.command, "ls");
+                assert_eq!(bash.timeout, Some(5000));
+                assert!(!bash.run_in_background);
+            }
+            other => panic!("expected Bash, got {other:?}"),
+        }
+
+        let grep = parse_tool_input(
+            "Grep",
+            &json!({"pattern": "fn main", "type": "rust", "-i": true, "-C": 2}),
+        );
+        let BuiltinToolInput::Grep(grep) = grep else {
+            panic!("expected Grep");
+        };
+        assert_eq!(grep.file_type.as_deref(), Some("rust"));
+        assert!(grep.case_insensitive);
+        assert_eq!(grep.context, Some(2));
+
+        let todos = parse_tool_input(
+            "TodoWrite",
+            &json!({"todos": [{"content": "Test", "status": "in_progress", "activeForm": "Testing"}]}),
+        );
+        let BuiltinToolInput::TodoWrite(todos) = todos else {
+            panic!("expected TodoWrite");
+        };
+        assert_eq!(todos.todos[0].status, TodoStatus::InProgress);
+        assert_eq!(todos.todos[0].active_form.as_deref(), Some("Testing"));
+
+        let edit = parse_tool_input(
+            "Edit",
+            &json!({"file_path": "a.rs", "old_string": "a", "new_string": "b"}),
+        );
+        assert_eq!(edit.path(), Some("a.rs"));
+    }
+
+    #[test]
+    fn unknown_or_malformed_input_falls_back_to_other() {
+        let input = json!({"query": "rust"});
+        assert_eq!(
+            parse_tool_input("mcp__docs__search", &input),
+            BuiltinToolInput::Other(input.clone())
+        );
+        // `Read` without a `file_path`.
+        assert_eq!(
+            parse_tool_input("Read", &input),
+            BuiltinToolInput::Other(input)
+        );
+    }
+}